@@ -0,0 +1,47 @@
+//! Converts a CIF extract into JSON Lines
+//!
+//! Reads the extract named on the command line, or standard input when there
+//! is none or it is `-`, and writes an object per line to standard output.
+//! With the `gzip` feature, compressed extracts are decompressed as they are
+//! read.
+
+use std::{
+    env, fs,
+    io::{self, BufRead, BufReader, BufWriter},
+    process::ExitCode,
+};
+
+fn main() -> ExitCode {
+    let mut args = env::args().skip(1);
+
+    let path = args.next();
+
+    if args.next().is_some() || path.as_deref().is_some_and(|path| path.starts_with("--")) {
+        eprintln!("usage: cif-jsonl [FILE]");
+        return ExitCode::FAILURE;
+    }
+
+    match run(path.as_deref()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("cif-jsonl: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(path: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let reader: Box<dyn BufRead> = match path {
+        None | Some("-") => Box::new(io::stdin().lock()),
+        Some(path) => Box::new(BufReader::new(fs::File::open(path)?)),
+    };
+
+    #[cfg(feature = "gzip")]
+    let reader = cify::reader::Decompress::new(reader)?;
+
+    let writer = BufWriter::new(io::stdout().lock());
+
+    cify::json::write_json_lines(reader, writer)?;
+
+    Ok(())
+}
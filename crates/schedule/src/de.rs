@@ -0,0 +1,487 @@
+//! Deserializing extracts with serde
+
+use std::str::FromStr;
+
+use serde::de::{self, DeserializeSeed, SeqAccess};
+
+use crate::{
+    error::{self, Diagnostic, Error, RecordParsingError, Result},
+    extract,
+    reader::{Mode, Skipped, SCHEDULE_IDENTITIES},
+};
+
+impl<'de> Deserializer<'de> {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(input: &'de str) -> Self {
+        Self::with_mode(input, Mode::Strict)
+    }
+
+    pub fn with_mode(input: &'de str, mode: Mode) -> Self {
+        Deserializer {
+            original: input,
+            input,
+            mode,
+            skipped: Skipped::default(),
+            checked: 0,
+            line: 1,
+            other_record: false,
+            exhausted: false,
+        }
+    }
+
+    /// Lines skipped so far in [Mode::Lenient]
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.skipped.diagnostics
+    }
+
+    pub fn into_diagnostics(self) -> Vec<Diagnostic> {
+        self.skipped.diagnostics
+    }
+
+    /// Position of the remaining input within the whole input
+    fn offset(&self) -> usize {
+        self.original.len() - self.input.len()
+    }
+
+    /// Skips any malformed lines at the start of the remaining input,
+    /// recording a diagnostic for each, and returns whether any were
+    ///
+    /// Each line is only checked once, however many sequences start on it.
+    fn skip_malformed(&mut self) -> bool {
+        let remaining = self.input.len();
+
+        while self.offset() >= self.checked && !self.input.is_empty() {
+            let len = self.input.find('\n').unwrap_or(self.input.len());
+            let line = &self.input[..len];
+            let record =
+                extract::Record::from_str(line).map_err(|e| Error::record(self.line, line, e));
+
+            if let Ok(bs @ extract::Record::BS(_)) = &record {
+                if self.skip_schedule(bs) {
+                    continue;
+                }
+            }
+
+            if self.skipped.skip(record).is_some() {
+                self.checked = self.offset() + len + 1;
+                break;
+            }
+
+            self.input = &self.input[(len + 1).min(self.input.len())..];
+            self.line += 1;
+        }
+
+        self.input.len() != remaining
+    }
+
+    /// Skips the schedule starting with the basic schedule `bs` as a whole if
+    /// it can't be built once its malformed lines are skipped, returning
+    /// whether it was
+    fn skip_schedule(&mut self, bs: &extract::Record) -> bool {
+        let mut records = vec![Ok(bs.clone())];
+        let mut len = self.input.find('\n').unwrap_or(self.input.len());
+
+        for (number, line) in (self.line + 1..).zip(self.input.split('\n').skip(1)) {
+            if !SCHEDULE_IDENTITIES.contains(&line.get(..2).unwrap_or_default()) {
+                break;
+            }
+
+            records
+                .push(extract::Record::from_str(line).map_err(|e| Error::record(number, line, e)));
+            len += line.len() + 1;
+        }
+
+        let lines = records.len();
+
+        if self.skipped.skip_schedule(records).is_some() {
+            return false;
+        }
+
+        self.input = &self.input[(len + 1).min(self.input.len())..];
+        self.line += lines;
+
+        true
+    }
+
+    fn peek_char(&mut self) -> Result<char> {
+        self.input.chars().next().ok_or(Error::Eof)
+    }
+
+    // Consume the first character in the input.
+    fn next_char(&mut self) -> Result<char> {
+        let ch = self.peek_char()?;
+        self.input = &self.input[ch.len_utf8()..];
+        Ok(ch)
+    }
+
+    fn parse_string(&mut self) -> Result<&'de str> {
+        match self.input.find('\n') {
+            Some(len) => {
+                let s = &self.input[..len];
+                self.input = &self.input[len..];
+                Ok(s)
+            }
+            None => {
+                let len = self.input.len();
+                let s = &self.input[..len];
+                self.input = &self.input[len..];
+                Ok(s)
+            }
+        }
+    }
+
+    /// Reports an error as [Error::Incomplete] when a sequence had already
+    /// run out of input, such as an extract without its trailer
+    ///
+    /// Errors in a record itself are kept, as the record was there to read.
+    fn end_of_input(&self, error: Error) -> Error {
+        match error {
+            Error::Record { .. } => error,
+            _ if self.exhausted => Error::Incomplete,
+            _ => error,
+        }
+    }
+
+    /// Attaches the line number and record identity to an error parsing the
+    /// record on `line`, which starts at `start`
+    ///
+    /// The visitor only reports errors as text, so the line is parsed again
+    /// to recover the field which was invalid. This only happens once parsing
+    /// has already failed.
+    fn record_error(&self, line: usize, start: &'de str, error: Error) -> Error {
+        if let Error::Record { .. } = error {
+            return error;
+        }
+
+        let raw = start.lines().next().unwrap_or_default();
+
+        match extract::Record::from_str(raw) {
+            Err(source) => Error::record(line, raw, source),
+            Ok(_) => error,
+        }
+    }
+}
+
+pub struct Deserializer<'de> {
+    /// The whole input, used to locate the line an error occurred on
+    original: &'de str,
+    input: &'de str,
+    mode: Mode,
+    skipped: Skipped,
+    /// Offset up to which lines are known to be well formed, in
+    /// [Mode::Lenient]
+    checked: usize,
+    /// Line the remaining input starts on, starting from 1
+    line: usize,
+    /// Whether the last record was of a type the enum being read doesn't
+    /// have, which ends the sequence it is in
+    other_record: bool,
+    /// Whether a sequence has ended because the input ran out
+    exhausted: bool,
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
+    type Error = error::Error;
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char
+        byte_buf option unit unit_struct newtype_struct tuple tuple_struct
+        map struct ignored_any bytes
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_borrowed_str(self.parse_string()?)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_any<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        if self.input.lines().count() > 1 {
+            return self.deserialize_seq(visitor);
+        }
+
+        Err(de::Error::custom(
+            "unsupported type provided to deserializer, only str is supported",
+        ))
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_seq(LineSeparated::new(self))
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_enum(Enum::new(self))
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let id = self.input.get(..2).ok_or(Error::Eof)?;
+        self.input = &self.input[2..];
+
+        visitor.visit_str(id)
+    }
+}
+
+pub fn from_str<'a, T: de::Deserialize<'a>>(input: &'a str) -> error::Result<T> {
+    let mut deserializer = Deserializer::from_str(input);
+
+    T::deserialize(&mut deserializer).map_err(|e| deserializer.end_of_input(e))
+}
+
+/// Deserializes an extract, skipping any malformed lines
+///
+/// Returns a [Diagnostic] for each line skipped, so imperfect extracts can be
+/// read and reported on. A train schedule which can't be built without its
+/// malformed lines is skipped as a whole. Errors which aren't confined to
+/// malformed lines, such as a schedule without an origin location at all,
+/// still fail, as [Error::WithDiagnostics] when lines were skipped before
+/// them.
+pub fn from_str_lenient<'a, T: de::Deserialize<'a>>(
+    input: &'a str,
+) -> error::Result<(T, Vec<Diagnostic>)> {
+    let mut deserializer = Deserializer::with_mode(input, Mode::Lenient);
+
+    match T::deserialize(&mut deserializer) {
+        Ok(t) => Ok((t, deserializer.into_diagnostics())),
+        Err(e) => {
+            let error = deserializer.end_of_input(e);
+
+            Err(error.with_diagnostics(deserializer.into_diagnostics()))
+        }
+    }
+}
+
+/// Deserializes an extract from a reader
+///
+/// The whole extract is read into memory first, so large extracts are better
+/// read with a [RecordReader](crate::reader::RecordReader). With the `gzip`
+/// feature, compressed extracts are decompressed as they are read.
+pub fn from_reader<R: std::io::BufRead, T: de::DeserializeOwned>(reader: R) -> error::Result<T> {
+    #[cfg(feature = "gzip")]
+    let mut reader = crate::reader::Decompress::new(reader)?;
+    #[cfg(not(feature = "gzip"))]
+    let mut reader = reader;
+
+    let mut input = String::new();
+    std::io::Read::read_to_string(&mut reader, &mut input)?;
+
+    from_str(&input)
+}
+
+// In order to handle commas correctly when deserializing a JSON array or map,
+// we need to track whether we are on the first element or past the first
+// element.
+struct LineSeparated<'a, 'de: 'a> {
+    de: &'a mut Deserializer<'de>,
+    first: bool,
+}
+
+impl<'a, 'de> LineSeparated<'a, 'de> {
+    fn new(de: &'a mut Deserializer<'de>) -> Self {
+        LineSeparated { de, first: true }
+    }
+}
+
+// `SeqAccess` is provided to the `Visitor` to give it the ability to iterate
+// through elements of the sequence.
+impl<'de, 'a> SeqAccess<'de> for LineSeparated<'a, 'de> {
+    type Error = error::Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let mut input = self.de.input;
+        let mut line = self.de.line;
+
+        // Check if there are no more elements.
+        let peek_char = match self.de.peek_char() {
+            Ok(ch) => ch,
+            Err(Error::Eof) => {
+                self.de.exhausted = true;
+                return Ok(None);
+            }
+            Err(e) => return Err(e),
+        };
+
+        // Line ending is consumed before every element except the first. A
+        // nested sequence that matched no lines leaves the input untouched,
+        // so the newline is only skipped when one is actually present.
+        if !self.first && peek_char == '\n' {
+            self.de.next_char()?;
+            self.de.line += 1;
+        }
+
+        self.first = false;
+
+        // Skipped lines are never checked again, so a sequence ending on the
+        // line after them mustn't give them back
+        if self.de.mode == Mode::Lenient && self.de.skip_malformed() {
+            input = self.de.input;
+            line = self.de.line;
+
+            if input.is_empty() {
+                self.de.exhausted = true;
+                return Ok(None);
+            }
+        }
+
+        let start = self.de.input;
+        let start_line = self.de.line;
+        self.de.other_record = false;
+
+        let error = match seed.deserialize(&mut *self.de) {
+            Ok(value) => return Ok(Some(value)),
+            Err(error) => error,
+        };
+
+        // Anything going wrong after the first line of the element isn't
+        // down to the element not belonging in the sequence
+        let first_line = start.find('\n').unwrap_or(start.len());
+
+        if start.len() - self.de.input.len() > first_line {
+            // Such as a schedule without its terminating location, when the
+            // input ran out part way through
+            if self.de.input.is_empty() && !matches!(error, Error::Record { .. }) {
+                return Err(Error::Incomplete);
+            }
+
+            return Err(error);
+        }
+
+        // A record of another type ends the sequence, and is left for
+        // whatever follows it to read
+        if std::mem::take(&mut self.de.other_record) {
+            self.de.input = input;
+            self.de.line = line;
+            return Ok(None);
+        }
+
+        match self.de.record_error(start_line, start, error) {
+            // A final line cut short means the extract was truncated
+            Error::Record {
+                source: RecordParsingError::InvalidLength,
+                ..
+            } if !start.contains('\n') => Err(Error::Incomplete),
+            error @ Error::Record { .. } => Err(error),
+            // A well formed record of another type ends the sequence
+            _ => {
+                self.de.input = input;
+                self.de.line = line;
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Identities of every type of record
+const IDENTITIES: [&str; 14] = [
+    "HD", "TI", "TA", "TD", "AA", "BS", "BX", "TN", "LO", "LI", "CR", "LT", "LN", "ZZ",
+];
+
+struct Enum<'a, 'de: 'a> {
+    de: &'a mut Deserializer<'de>,
+    /// The line the record is on, from its identity onwards
+    start: &'de str,
+    line: usize,
+}
+
+impl<'a, 'de> Enum<'a, 'de> {
+    fn new(de: &'a mut Deserializer<'de>) -> Self {
+        let start = de.input;
+        let line = de.line;
+
+        Enum { de, start, line }
+    }
+}
+
+// `EnumAccess` is provided to the `Visitor` to give it the ability to determine
+// which variant of the enum is supposed to be deserialized.
+//
+// Note that all enum deserialization methods in Serde refer exclusively to the
+// "externally tagged" enum representation.
+impl<'de, 'a> de::EnumAccess<'de> for Enum<'a, 'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match seed.deserialize(&mut *self.de) {
+            Ok(val) => Ok((val, self)),
+            Err(error) => {
+                let identity = self.start.get(..2).unwrap_or_default();
+                self.de.other_record = IDENTITIES.contains(&identity);
+
+                Err(error)
+            }
+        }
+    }
+}
+
+// `VariantAccess` is provided to the `Visitor` to give it the ability to see
+// the content of the single variant that it decided to deserialize.
+impl<'de, 'a> de::VariantAccess<'de> for Enum<'a, 'de> {
+    type Error = Error;
+
+    // If the `Visitor` expected this variant to be a unit variant, the input
+    // should have been the plain string case handled in `deserialize_enum`.
+    fn unit_variant(self) -> Result<()> {
+        let _ = self.de.parse_string()?;
+
+        Ok(())
+    }
+
+    // Newtype variants are represented in JSON as `{ NAME: VALUE }` so
+    // deserialize the value here.
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(&mut *self.de)
+            .map_err(|e| self.de.record_error(self.line, self.start, e))
+    }
+
+    // Tuple variants are represented in JSON as `{ NAME: [DATA...] }` so
+    // deserialize the sequence of data here.
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        de::Deserializer::deserialize_seq(self.de, visitor)
+    }
+
+    // Struct variants are represented in JSON as `{ NAME: { K: V, ... } }` so
+    // deserialize the inner map here.
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        de::Deserializer::deserialize_map(self.de, visitor)
+    }
+}
@@ -1,7 +1,9 @@
-use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
 use thiserror::Error;
 
-#[derive(Serialize, Deserialize, Debug, Error)]
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Error {
     #[error("Invalid length")]
     InvalidLength,
@@ -11,24 +13,139 @@ pub enum Error {
     Eof,
     #[error("End of sequence")]
     Eos,
+    #[error("Incomplete extract, the trailer record was not found")]
+    Incomplete,
+    #[error("I/O error: {0}")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Io(#[from] std::io::Error),
+    #[error("line {line}: {identity} record: {source}")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Record {
+        /// Line of the extract the record is on, starting from 1
+        line: usize,
+        /// Record identity, such as `BS`
+        identity: String,
+        source: RecordParsingError,
+    },
+    #[error("{source} (after skipping {} malformed lines)", diagnostics.len())]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    WithDiagnostics {
+        source: Box<Error>,
+        /// Lines skipped before the error, which may have caused it
+        diagnostics: Vec<Diagnostic>,
+    },
+}
+
+impl Error {
+    /// Attaches the line number and identity of the record to an error
+    /// parsing it
+    pub(crate) fn record(line: usize, raw: &str, source: RecordParsingError) -> Self {
+        Error::Record {
+            line,
+            identity: raw.get(0..2).unwrap_or(raw).to_string(),
+            source,
+        }
+    }
+
+    /// Keeps the lines skipped before an error along with it, as they may be
+    /// what caused it
+    pub(crate) fn with_diagnostics(self, diagnostics: Vec<Diagnostic>) -> Self {
+        if diagnostics.is_empty() {
+            return self;
+        }
+
+        Error::WithDiagnostics {
+            source: Box::new(self),
+            diagnostics,
+        }
+    }
+
+    /// Turns an error in a single record into a diagnostic, so the record can
+    /// be skipped
+    pub(crate) fn into_diagnostic(self) -> std::result::Result<Diagnostic, Error> {
+        match self {
+            Error::Record {
+                line,
+                identity,
+                source,
+            } => Ok(Diagnostic {
+                line,
+                identity,
+                reason: source,
+            }),
+            error => Err(error),
+        }
+    }
+
+    pub(crate) fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Error::Syntax(msg.to_string())
+    }
+
+    pub(crate) fn missing_field(field: &'static str) -> Self {
+        Error::Syntax(format!("missing field `{field}`"))
+    }
 }
 
 #[derive(Debug, Error)]
 pub enum RecordParsingError {
     #[error("invalid length")]
     InvalidLength,
-    #[error("invalid field {0}: {1}")]
-    InvalidField(&'static str, String),
+    #[error(
+        "invalid {field} {value:?} in columns {}-{}",
+        columns.start + 1,
+        columns.end
+    )]
+    InvalidField {
+        field: &'static str,
+        /// Columns of the line the field occupies, starting from 0 and
+        /// including the record identity
+        columns: Range<usize>,
+        value: String,
+    },
     #[error("non-ascii characters encountered")]
     NonAscii,
     #[error("unexpected record identity, expecting {0}")]
     UnexpectedRecordIdentity(&'static str),
+    #[error("unknown record identity {0:?}")]
+    UnknownRecordIdentity(String),
 }
 
+impl RecordParsingError {
+    /// Error for the field in `range` of a record with its identity stripped
+    pub(crate) fn invalid_field(field: &'static str, stripped: &str, range: Range<usize>) -> Self {
+        RecordParsingError::InvalidField {
+            field,
+            value: stripped[range.clone()].to_string(),
+            columns: range.start + 2..range.end + 2,
+        }
+    }
+}
+
+/// A malformed line skipped while parsing leniently
+#[derive(Debug, Error)]
+#[error("line {line}: {identity} record: {reason}")]
+pub struct Diagnostic {
+    /// Line of the extract, starting from 1
+    pub line: usize,
+    /// Record identity, such as `BS`
+    pub identity: String,
+    pub reason: RecordParsingError,
+}
+
+/// Error returned when a fixed set of codes does not contain the given value
+#[derive(Debug, Error)]
+#[error("unrecognised code {0:?}")]
+pub struct InvalidCode(pub String);
+
 pub type Result<T> = std::result::Result<T, Error>;
 
+#[cfg(feature = "serde")]
 impl serde::de::Error for Error {
     fn custom<T: std::fmt::Display>(msg: T) -> Self {
-        Error::Syntax(msg.to_string())
+        Error::custom(msg)
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        Error::missing_field(field)
     }
 }
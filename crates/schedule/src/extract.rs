@@ -1,18 +1,30 @@
+pub mod activity;
 pub mod association;
+pub mod characteristics;
 pub mod header;
 pub mod identifier;
 pub mod location;
 pub mod schedule;
+pub mod time;
 pub mod tiploc;
+pub mod trailer;
 
+pub use activity::*;
 pub use association::*;
+pub use characteristics::*;
 pub use header::*;
 pub use identifier::*;
 pub use location::*;
 pub use schedule::*;
+pub use time::*;
 pub use tiploc::*;
+pub use trailer::*;
 
-use serde::{self, Deserialize};
+use std::{fmt, ops::Range, str::FromStr};
+
+use chrono::{NaiveDate, Weekday};
+
+use crate::error::{InvalidCode, RecordParsingError};
 
 /// Determines how the record should be interpreted in
 /// relation to another instance of the same record
@@ -21,45 +33,311 @@ use serde::{self, Deserialize};
 /// If there was a previous record that created a `BS`
 /// record with [TransactionType::New] then a
 /// [TransactionType::Delete] would remove that schedule.
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TransactionType {
-    #[serde(rename = "N")]
+    #[cfg_attr(feature = "serde", serde(rename = "N"))]
     New,
-    #[serde(rename = "R")]
+    #[cfg_attr(feature = "serde", serde(rename = "R"))]
     Revise,
-    #[serde(rename = "D")]
+    #[cfg_attr(feature = "serde", serde(rename = "D"))]
     Delete,
 }
 
+impl TransactionType {
+    /// The code used in CIF
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::New => "N",
+            Self::Revise => "R",
+            Self::Delete => "D",
+        }
+    }
+}
+
+impl FromStr for TransactionType {
+    type Err = InvalidCode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "N" => Self::New,
+            "R" => Self::Revise,
+            "D" => Self::Delete,
+            _ => return Err(InvalidCode(s.to_string())),
+        })
+    }
+}
+
 /// Type representing the possible record types within a CIF extract
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Record {
     /// Header
     HD(Header),
     /// TIPLOC Insert
     TI(TiplocInsert),
     /// TIPLOC Amend
-    TA(String),
+    TA(TiplocAmend),
     /// TIPLOC Delete
-    TD(String),
+    TD(TiplocDelete),
     /// Association
-    AA,
+    AA(Association),
     /// Basic Schedule
     BS(BasicSchedule),
     /// Basic Schedule Extra Details
-    BX(String),
+    BX(BasicScheduleExtra),
     /// Train Specific Note (Unused)
-    TN(String),
+    TN(#[cfg_attr(feature = "serde", serde(serialize_with = "serialize_text"))] String),
     /// Origin Location
-    LO(String),
+    LO(OriginLocation),
     /// Intermediate Location
-    LI(String),
+    LI(IntermediateLocation),
     /// Changes en Route
-    CR(String),
+    CR(ChangeEnRoute),
     /// Terminating Location
-    LT(String),
+    LT(TerminatingLocation),
     /// Location Specific Note (Unused)
-    LN(String),
+    LN(#[cfg_attr(feature = "serde", serde(serialize_with = "serialize_text"))] String),
     /// Trailer
-    ZZ,
+    ZZ(Trailer),
+}
+
+impl Record {
+    /// The record identity, such as `BS`
+    pub fn identity(&self) -> &'static str {
+        match self {
+            Record::HD(_) => "HD",
+            Record::TI(_) => "TI",
+            Record::TA(_) => "TA",
+            Record::TD(_) => "TD",
+            Record::AA(_) => "AA",
+            Record::BS(_) => "BS",
+            Record::BX(_) => "BX",
+            Record::TN(_) => "TN",
+            Record::LO(_) => "LO",
+            Record::LI(_) => "LI",
+            Record::CR(_) => "CR",
+            Record::LT(_) => "LT",
+            Record::LN(_) => "LN",
+            Record::ZZ(_) => "ZZ",
+        }
+    }
+}
+
+impl fmt::Display for Record {
+    /// Writes the record as a line of CIF
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Record::HD(record) => record.fmt(f),
+            Record::TI(record) => record.fmt(f),
+            Record::TA(record) => record.fmt(f),
+            Record::TD(record) => record.fmt(f),
+            Record::AA(record) => record.fmt(f),
+            Record::BS(record) => record.fmt(f),
+            Record::BX(record) => record.fmt(f),
+            Record::TN(note) => write!(f, "TN{note}"),
+            Record::LO(record) => record.fmt(f),
+            Record::LI(record) => record.fmt(f),
+            Record::CR(record) => record.fmt(f),
+            Record::LT(record) => record.fmt(f),
+            Record::LN(note) => write!(f, "LN{note}"),
+            Record::ZZ(record) => record.fmt(f),
+        }
+    }
+}
+
+impl FromStr for Record {
+    type Err = RecordParsingError;
+
+    /// Parses a line of an extract, choosing the record type from its
+    /// identity
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.is_ascii() {
+            return Err(RecordParsingError::NonAscii);
+        }
+
+        let identity = s.get(0..2).ok_or(RecordParsingError::InvalidLength)?;
+
+        Ok(match identity {
+            "HD" => Record::HD(s.parse()?),
+            "TI" => Record::TI(s.parse()?),
+            "TA" => Record::TA(s.parse()?),
+            "TD" => Record::TD(s.parse()?),
+            "AA" => Record::AA(s.parse()?),
+            "BS" => Record::BS(s.parse()?),
+            "BX" => Record::BX(s.parse()?),
+            "TN" => Record::TN(s[2..].to_string()),
+            "LO" => Record::LO(s.parse()?),
+            "LI" => Record::LI(s.parse()?),
+            "CR" => Record::CR(s.parse()?),
+            "LT" => Record::LT(s.parse()?),
+            "LN" => Record::LN(s[2..].to_string()),
+            "ZZ" => Record::ZZ(s.parse()?),
+            _ => {
+                return Err(RecordParsingError::UnknownRecordIdentity(
+                    identity.to_string(),
+                ))
+            }
+        })
+    }
+}
+
+/// Decodes a seven character days run field, where each position from Monday
+/// to Sunday is `1` when the train runs on that day
+pub(crate) fn parse_days(field: &str) -> Vec<Weekday> {
+    field
+        .chars()
+        .enumerate()
+        .filter_map(|(i, value)| match value {
+            '1' => Weekday::try_from(i as u8).ok(),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Parses a field that is left blank on some records, such as deletions
+pub(crate) fn parse_optional<T: FromStr>(field: &str) -> Result<Option<T>, T::Err> {
+    if field.trim().is_empty() {
+        return Ok(None);
+    }
+
+    field.parse().map(Some)
+}
+
+/// Parses a free text field, dropping the padding and treating a blank field
+/// as absent
+pub(crate) fn parse_text(field: &str) -> Option<String> {
+    let trimmed = field.trim();
+
+    (!trimmed.is_empty()).then(|| trimmed.to_string())
+}
+
+/// Serializes a fixed width text field without its padding, or as `null`
+/// when it is blank, in the same way as [parse_text]
+#[cfg(feature = "serde")]
+pub(crate) fn serialize_text<T, S>(text: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: AsRef<str> + ?Sized,
+    S: serde::Serializer,
+{
+    match text.as_ref().trim() {
+        "" => serializer.serialize_none(),
+        text => serializer.serialize_some(text),
+    }
+}
+
+/// Serializes a single character field, or `null` when it is blank
+#[cfg(feature = "serde")]
+pub(crate) fn serialize_character<S: serde::Serializer>(
+    character: &char,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match character {
+        ' ' => serializer.serialize_none(),
+        character => serializer.serialize_some(character),
+    }
+}
+
+/// Parses a single character location suffix, which is blank when unused
+pub(crate) fn parse_suffix(field: &str) -> Option<char> {
+    field.chars().next().filter(|c| *c != ' ')
+}
+
+/// Writes a fixed width field, left aligned and padded with spaces
+pub(crate) fn write_field(
+    f: &mut fmt::Formatter<'_>,
+    value: impl fmt::Display,
+    width: usize,
+) -> fmt::Result {
+    write!(f, "{:<width$}", value.to_string())
+}
+
+/// Writes a field which is left blank when there is no value
+pub(crate) fn write_optional(
+    f: &mut fmt::Formatter<'_>,
+    value: Option<impl fmt::Display>,
+    width: usize,
+) -> fmt::Result {
+    match value {
+        Some(value) => write_field(f, value, width),
+        None => write_field(f, "", width),
+    }
+}
+
+/// The fields of the line a record was read from, kept so that fields which
+/// CIF allows to be laid out more than one way are written back as they were
+/// read, such as allowances and activities
+///
+/// It takes no part in comparisons, so records holding the same values are
+/// equal however their lines were laid out.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SourceLine(Option<Box<str>>);
+
+impl SourceLine {
+    /// Keeps the fields of a line, following its record identity
+    pub(crate) fn new(stripped: &str) -> Self {
+        SourceLine(Some(stripped.into()))
+    }
+
+    /// Writes a field as it was laid out in the source line, as long as it
+    /// still reads as `value`, or with `write` otherwise
+    pub(crate) fn write_field<T: PartialEq>(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        range: Range<usize>,
+        value: &T,
+        parse: impl FnOnce(&str) -> Option<T>,
+        write: impl FnOnce(&mut fmt::Formatter<'_>) -> fmt::Result,
+    ) -> fmt::Result {
+        match self.0.as_deref().and_then(|line| line.get(range)) {
+            Some(text) if parse(text).as_ref() == Some(value) => f.write_str(text),
+            _ => write(f),
+        }
+    }
+}
+
+impl PartialEq for SourceLine {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for SourceLine {}
+
+/// Writes a date in the `yymmdd` form used by schedules and associations
+pub(crate) fn write_date(f: &mut fmt::Formatter<'_>, date: NaiveDate) -> fmt::Result {
+    write!(f, "{}", date.format("%y%m%d"))
+}
+
+/// Writes the last date of a schedule or association, which deletions leave
+/// blank when it is the same as the first
+pub(crate) fn write_end_date(
+    f: &mut fmt::Formatter<'_>,
+    transaction_type: TransactionType,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> fmt::Result {
+    if transaction_type == TransactionType::Delete && from == to {
+        return write_field(f, "", 6);
+    }
+
+    write_date(f, to)
+}
+
+/// Encodes days run as seven characters from Monday to Sunday, the reverse
+/// of [parse_days]
+///
+/// Deletions leave the field blank, so no days at all are written as blank.
+pub(crate) fn write_days(f: &mut fmt::Formatter<'_>, days: &[Weekday]) -> fmt::Result {
+    if days.is_empty() {
+        return write_field(f, "", 7);
+    }
+
+    for i in 0..7 {
+        let runs = days.iter().any(|day| day.num_days_from_monday() == i);
+
+        f.write_str(if runs { "1" } else { "0" })?;
+    }
+
+    Ok(())
 }
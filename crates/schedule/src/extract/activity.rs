@@ -0,0 +1,238 @@
+use std::{convert::Infallible, fmt, ops::Range, str::FromStr};
+
+use super::{characteristics::code_table, write_field, SourceLine};
+
+code_table! {
+    /// Activity performed by a train at a location
+    pub enum Activity {
+        /// Stops or shunts for other trains to pass (`A`)
+        StopsForOtherTrainsToPass = "A",
+        /// Attach or detach an assisting locomotive (`AE`)
+        AttachDetachAssistingLocomotive = "AE",
+        /// Shows as 'X' on arrival (`AX`)
+        ShowsAsXOnArrival = "AX",
+        /// Stops for banking locomotive (`BL`)
+        StopsForBankingLocomotive = "BL",
+        /// Stops to change trainmen (`C`)
+        StopsToChangeTrainmen = "C",
+        /// Stops to set down passengers (`D`)
+        StopsToSetDown = "D",
+        /// Stops to detach vehicles (`-D`)
+        StopsToDetachVehicles = "-D",
+        /// Stops for examination (`E`)
+        StopsForExamination = "E",
+        /// National Rail Timetable data to add (`G`)
+        NationalRailTimetableDataToAdd = "G",
+        /// Notional activity to prevent WTT timing columns merging (`H`)
+        PreventColumnMerge = "H",
+        /// As `H`, where a third column is involved (`HH`)
+        PreventThirdColumnMerge = "HH",
+        /// Passenger count point (`K`)
+        PassengerCountPoint = "K",
+        /// Ticket collection and examination point (`KC`)
+        TicketCollectionAndExaminationPoint = "KC",
+        /// Ticket examination point (`KE`)
+        TicketExaminationPoint = "KE",
+        /// Ticket examination point, First Class only (`KF`)
+        FirstClassTicketExaminationPoint = "KF",
+        /// Selective ticket examination point (`KS`)
+        SelectiveTicketExaminationPoint = "KS",
+        /// Stops to change locomotives (`L`)
+        StopsToChangeLocomotives = "L",
+        /// Stop not advertised (`N`)
+        StopNotAdvertised = "N",
+        /// Stops for other operating reasons (`OP`)
+        StopsForOtherOperatingReasons = "OP",
+        /// Train locomotive on rear (`OR`)
+        LocomotiveOnRear = "OR",
+        /// Propelling between points shown (`PR`)
+        Propelling = "PR",
+        /// Stops when required (`R`)
+        StopsWhenRequired = "R",
+        /// Reversing movement, or driver changes ends (`RM`)
+        ReversingMovement = "RM",
+        /// Stops for locomotive to run round train (`RR`)
+        StopsForLocomotiveToRunRound = "RR",
+        /// Stops for railway personnel only (`S`)
+        StopsForRailwayPersonnelOnly = "S",
+        /// Stops to take up and set down passengers (`T`)
+        StopsToTakeUpAndSetDown = "T",
+        /// Stops to attach and detach vehicles (`-T`)
+        StopsToAttachAndDetachVehicles = "-T",
+        /// Train begins (`TB`)
+        TrainBegins = "TB",
+        /// Train finishes (`TF`)
+        TrainFinishes = "TF",
+        /// Detail consist for TOPS Direct requested by EWS (`TS`)
+        DetailConsistForTopsDirect = "TS",
+        /// Stops, or passes, for tablet, staff or token (`TW`)
+        StopsForTabletStaffOrToken = "TW",
+        /// Stops to take up passengers (`U`)
+        StopsToTakeUp = "U",
+        /// Stops to attach vehicles (`-U`)
+        StopsToAttachVehicles = "-U",
+        /// Stops for watering of coaches (`W`)
+        StopsForWatering = "W",
+        /// Passes another train at a crossing point on a single line (`X`)
+        PassesAtCrossingPoint = "X",
+    }
+}
+
+/// The set of activities at a location
+///
+/// Decoded from the twelve character activity field, which holds up to six
+/// two character codes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Activities(Vec<Activity>);
+
+impl Activities {
+    pub fn contains(&self, activity: &Activity) -> bool {
+        self.0.contains(activity)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Activity> {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether passengers may board the train here
+    ///
+    /// Request stops and the start of the train count as picking up.
+    pub fn picks_up(&self) -> bool {
+        self.iter().any(|activity| {
+            matches!(
+                activity,
+                Activity::StopsToTakeUpAndSetDown
+                    | Activity::StopsToTakeUp
+                    | Activity::StopsWhenRequired
+                    | Activity::TrainBegins
+            )
+        })
+    }
+
+    /// Whether passengers may alight from the train here
+    ///
+    /// Request stops and the end of the train count as setting down.
+    pub fn sets_down(&self) -> bool {
+        self.iter().any(|activity| {
+            matches!(
+                activity,
+                Activity::StopsToTakeUpAndSetDown
+                    | Activity::StopsToSetDown
+                    | Activity::StopsWhenRequired
+                    | Activity::TrainFinishes
+            )
+        })
+    }
+
+    /// Whether the location is an advertised stop for passengers
+    ///
+    /// Only the passenger activities count. The start and end of the train
+    /// (`TB` and `TF`) are given for empty stock and freight trains too, so
+    /// whether those are public stops depends on the schedule's
+    /// [train category](super::TrainCategory::is_passenger).
+    pub fn is_public_stop(&self) -> bool {
+        !self.contains(&Activity::StopNotAdvertised)
+            && self.iter().any(|activity| {
+                matches!(
+                    activity,
+                    Activity::StopsToTakeUpAndSetDown
+                        | Activity::StopsToTakeUp
+                        | Activity::StopsToSetDown
+                        | Activity::StopsWhenRequired
+                )
+            })
+    }
+}
+
+impl FromStr for Activities {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let activities = s
+            .as_bytes()
+            .chunks(2)
+            .filter_map(|chunk| std::str::from_utf8(chunk).ok())
+            .map(str::trim)
+            .filter(|code| !code.is_empty())
+            .map(|code| code.parse())
+            .collect::<Result<_, _>>()?;
+
+        Ok(Activities(activities))
+    }
+}
+
+impl fmt::Display for Activities {
+    /// Writes the activities in their fixed width form, each code padded to
+    /// two characters
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for activity in self.iter() {
+            write!(f, "{:<2}", activity.code())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> IntoIterator for &'a Activities {
+    type Item = &'a Activity;
+    type IntoIter = std::slice::Iter<'a, Activity>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+/// Writes a twelve character activity field as it was laid out when read, or
+/// with the codes packed together
+pub(crate) fn write_activities(
+    f: &mut fmt::Formatter<'_>,
+    source: &SourceLine,
+    range: Range<usize>,
+    activities: &Activities,
+) -> fmt::Result {
+    source.write_field(
+        f,
+        range,
+        activities,
+        |text| text.parse().ok(),
+        |f| write_field(f, activities, 12),
+    )
+}
+
+#[test]
+fn parse_activities() {
+    let activities: Activities = "T -DRM      ".parse().unwrap();
+
+    assert_eq!(
+        activities.iter().cloned().collect::<Vec<_>>(),
+        vec![
+            Activity::StopsToTakeUpAndSetDown,
+            Activity::StopsToDetachVehicles,
+            Activity::ReversingMovement
+        ]
+    );
+    assert!(activities.is_public_stop());
+    assert_eq!(activities.to_string(), "T -DRM");
+
+    let unadvertised: Activities = "U N         ".parse().unwrap();
+    assert!(unadvertised.picks_up());
+    assert!(!unadvertised.sets_down());
+    assert!(!unadvertised.is_public_stop());
+
+    let origin: Activities = "TB          ".parse().unwrap();
+    assert!(origin.picks_up());
+    assert!(!origin.is_public_stop());
+
+    let passing: Activities = "            ".parse().unwrap();
+    assert!(passing.is_empty());
+    assert!(!passing.is_public_stop());
+}
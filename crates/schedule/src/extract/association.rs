@@ -1 +1,265 @@
-pub struct Association;
+use std::{fmt, str::FromStr};
+
+use crate::error::{InvalidCode, RecordParsingError};
+use chrono::{NaiveDate, Weekday};
+
+use super::{
+    location::Tiploc, parse_days, parse_optional, parse_suffix, write_date, write_days,
+    write_end_date, write_field, write_optional, StpIndicator, TransactionType,
+};
+
+/// How the two trains in an association relate to each other
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AssociationCategory {
+    /// The associated train joins the base train (`JJ`)
+    #[cfg_attr(feature = "serde", serde(rename = "JJ"))]
+    Join,
+    /// The associated train divides from the base train (`VV`)
+    #[cfg_attr(feature = "serde", serde(rename = "VV"))]
+    Divide,
+    /// The associated train is the next working of the base train (`NP`)
+    #[cfg_attr(feature = "serde", serde(rename = "NP"))]
+    Next,
+}
+
+impl AssociationCategory {
+    /// The code used in CIF
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Join => "JJ",
+            Self::Divide => "VV",
+            Self::Next => "NP",
+        }
+    }
+}
+
+impl FromStr for AssociationCategory {
+    type Err = InvalidCode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "JJ" => Self::Join,
+            "VV" => Self::Divide,
+            "NP" => Self::Next,
+            _ => return Err(InvalidCode(s.to_string())),
+        })
+    }
+}
+
+/// When the association happens relative to the departure of the base train
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DateIndicator {
+    /// Same day as the base train (`S`)
+    #[cfg_attr(feature = "serde", serde(rename = "S"))]
+    Standard,
+    /// The day after the base train departs (`N`)
+    #[cfg_attr(feature = "serde", serde(rename = "N"))]
+    OverNextMidnight,
+    /// The day before the base train departs (`P`)
+    #[cfg_attr(feature = "serde", serde(rename = "P"))]
+    OverPreviousMidnight,
+}
+
+impl DateIndicator {
+    /// The code used in CIF
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Standard => "S",
+            Self::OverNextMidnight => "N",
+            Self::OverPreviousMidnight => "P",
+        }
+    }
+}
+
+impl FromStr for DateIndicator {
+    type Err = InvalidCode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "S" => Self::Standard,
+            "N" => Self::OverNextMidnight,
+            "P" => Self::OverPreviousMidnight,
+            _ => return Err(InvalidCode(s.to_string())),
+        })
+    }
+}
+
+/// Whether the association is relevant to passengers or only to operations
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AssociationType {
+    #[cfg_attr(feature = "serde", serde(rename = "P"))]
+    Passenger,
+    #[cfg_attr(feature = "serde", serde(rename = "O"))]
+    Operating,
+}
+
+impl AssociationType {
+    /// The code used in CIF
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Passenger => "P",
+            Self::Operating => "O",
+        }
+    }
+}
+
+impl FromStr for AssociationType {
+    type Err = InvalidCode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "P" => Self::Passenger,
+            "O" => Self::Operating,
+            _ => return Err(InvalidCode(s.to_string())),
+        })
+    }
+}
+
+/// Association Record
+///
+/// Links a base train to an associated train at a location, describing a
+/// join, divide or next working. Delete records only populate the key fields,
+/// so the category, date indicator and association type are optional.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Association {
+    pub transaction_type: TransactionType,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "super::serialize_text"))]
+    pub base_uid: String,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "super::serialize_text"))]
+    pub associated_uid: String,
+    pub from: NaiveDate,
+    /// Last date of the association, which deletions may leave blank, in
+    /// which case it is the same as `from`
+    pub to: NaiveDate,
+    pub days: Vec<Weekday>,
+    pub category: Option<AssociationCategory>,
+    pub date_indicator: Option<DateIndicator>,
+    pub location: Tiploc,
+    pub base_location_suffix: Option<char>,
+    pub associated_location_suffix: Option<char>,
+    /// Always `T` where given
+    pub diagram_type: Option<char>,
+    pub association_type: Option<AssociationType>,
+    pub stp_indicator: StpIndicator,
+}
+
+impl FromStr for Association {
+    type Err = RecordParsingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.is_ascii() {
+            return Err(RecordParsingError::NonAscii);
+        }
+
+        let stripped = match s.len() {
+            78 => s,
+            80 => {
+                if &s[0..2] != "AA" {
+                    return Err(RecordParsingError::UnexpectedRecordIdentity("AA"));
+                }
+
+                &s[2..]
+            }
+            _ => return Err(RecordParsingError::InvalidLength),
+        };
+
+        let transaction_type: TransactionType = stripped[0..1]
+            .parse()
+            .map_err(|_| RecordParsingError::invalid_field("Transaction Type", stripped, 0..1))?;
+
+        let from = NaiveDate::parse_from_str(&stripped[13..19], "%y%m%d").map_err(|_| {
+            RecordParsingError::invalid_field("Association Start Date", stripped, 13..19)
+        })?;
+
+        let to = match &stripped[19..25] {
+            "      " if transaction_type == TransactionType::Delete => from,
+            field => NaiveDate::parse_from_str(field, "%y%m%d").map_err(|_| {
+                RecordParsingError::invalid_field("Association End Date", stripped, 19..25)
+            })?,
+        };
+
+        Ok(Association {
+            transaction_type,
+            base_uid: stripped[1..7].to_string(),
+            associated_uid: stripped[7..13].to_string(),
+            from,
+            to,
+            days: parse_days(&stripped[25..32]),
+            category: parse_optional(&stripped[32..34]).map_err(|_| {
+                RecordParsingError::invalid_field("Association Category", stripped, 32..34)
+            })?,
+            date_indicator: parse_optional(&stripped[34..35]).map_err(|_| {
+                RecordParsingError::invalid_field("Association Date Indicator", stripped, 34..35)
+            })?,
+            location: stripped[35..42].parse().map_err(|_| {
+                RecordParsingError::invalid_field("Association Location", stripped, 35..42)
+            })?,
+            base_location_suffix: parse_suffix(&stripped[42..43]),
+            associated_location_suffix: parse_suffix(&stripped[43..44]),
+            diagram_type: parse_suffix(&stripped[44..45]),
+            association_type: parse_optional(&stripped[45..46]).map_err(|_| {
+                RecordParsingError::invalid_field("Association Type", stripped, 45..46)
+            })?,
+            stp_indicator: stripped[77..].parse().map_err(|_| {
+                RecordParsingError::invalid_field("STP Indicator", stripped, 77..78)
+            })?,
+        })
+    }
+}
+
+impl fmt::Display for Association {
+    /// Writes the record as a line of CIF
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AA{}", self.transaction_type.code())?;
+        write_field(f, &self.base_uid, 6)?;
+        write_field(f, &self.associated_uid, 6)?;
+        write_date(f, self.from)?;
+        write_end_date(f, self.transaction_type, self.from, self.to)?;
+        write_days(f, &self.days)?;
+        write_optional(f, self.category.as_ref().map(|c| c.code()), 2)?;
+        write_optional(f, self.date_indicator.as_ref().map(|d| d.code()), 1)?;
+        write_field(f, &self.location, 7)?;
+        write_optional(f, self.base_location_suffix, 1)?;
+        write_optional(f, self.associated_location_suffix, 1)?;
+        write_optional(f, self.diagram_type, 1)?;
+        write_optional(f, self.association_type.as_ref().map(|t| t.code()), 1)?;
+        write_field(f, "", 31)?;
+        f.write_str(self.stp_indicator.code())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Association {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Self::from_str(serde::Deserialize::deserialize(deserializer)?)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[test]
+fn deserialize_aa() -> Result<(), Box<dyn std::error::Error>> {
+    let raw = "AANC12345C234562505182512131111100JJSMNCRPIC  TP                               P";
+
+    let association = Association::from_str(raw)?;
+
+    assert_eq!(association.base_uid, "C12345");
+    assert_eq!(association.associated_uid, "C23456");
+    assert_eq!(association.days.len(), 5);
+    assert_eq!(association.category, Some(AssociationCategory::Join));
+    assert_eq!(association.date_indicator, Some(DateIndicator::Standard));
+    assert_eq!(&*association.location, "MNCRPIC");
+    assert_eq!(association.base_location_suffix, None);
+    assert_eq!(
+        association.association_type,
+        Some(AssociationType::Passenger)
+    );
+
+    Ok(())
+}
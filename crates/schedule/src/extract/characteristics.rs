@@ -0,0 +1,382 @@
+use std::{convert::Infallible, fmt, ops::Range, str::FromStr};
+
+use super::{write_optional, SourceLine};
+
+/// Declares a code table enum, along with its conversions to and from the
+/// code used in CIF. Codes which aren't in the table are kept as `Unknown`, so
+/// newly introduced codes don't stop an extract from being read. With the
+/// `serde` feature, the enum serializes as its code.
+macro_rules! code_table {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident {
+            $($(#[$variant_meta:meta])* $variant:ident = $code:literal,)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($(#[$variant_meta])* $variant,)*
+            /// A code not known to this crate
+            Unknown(String),
+        }
+
+        impl $name {
+            /// The code used in CIF
+            pub fn code(&self) -> &str {
+                match self {
+                    $(Self::$variant => $code,)*
+                    Self::Unknown(code) => code,
+                }
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = Infallible;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Ok(match s.trim() {
+                    $($code => Self::$variant,)*
+                    code => Self::Unknown(code.to_string()),
+                })
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.code())
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for $name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.code())
+            }
+        }
+    };
+}
+
+pub(crate) use code_table;
+
+code_table! {
+    /// Category of train, from the RSPS5046 train category code table
+    pub enum TrainCategory {
+        /// London Underground or Metro service
+        LondonUndergroundOrMetro = "OL",
+        UnadvertisedOrdinaryPassenger = "OU",
+        OrdinaryPassenger = "OO",
+        StaffTrain = "OS",
+        Mixed = "OW",
+        ChannelTunnel = "XC",
+        /// Sleeper (Europe Night Services)
+        EuropeanSleeper = "XD",
+        International = "XI",
+        Motorail = "XR",
+        UnadvertisedExpress = "XU",
+        ExpressPassenger = "XX",
+        /// Sleeper (Domestic)
+        DomesticSleeper = "XZ",
+        ReplacementBus = "BR",
+        /// Bus running as a WTT service
+        WttBus = "BS",
+        EmptyCoachingStock = "EE",
+        /// Empty coaching stock, London Underground or Metro
+        EmptyCoachingStockLondonUndergroundOrMetro = "EL",
+        EmptyCoachingStockAndStaff = "ES",
+        Postal = "JJ",
+        PostOfficeControlledParcels = "PM",
+        Parcels = "PP",
+        /// Empty non-passenger carrying coaching stock
+        EmptyNpccs = "PV",
+        Departmental = "DD",
+        CivilEngineer = "DH",
+        MechanicalAndElectricalEngineer = "DI",
+        Stores = "DQ",
+        Test = "DT",
+        SignalAndTelecommunicationsEngineer = "DY",
+        LocomotiveAndBrakeVan = "ZB",
+        LightLocomotive = "ZZ",
+        AutomotiveComponents = "J2",
+        AutomotiveVehicles = "H2",
+        EdibleProducts = "J3",
+        IndustrialMinerals = "J4",
+        Chemicals = "J5",
+        BuildingMaterials = "J6",
+        GeneralMerchandise = "J8",
+        European = "H8",
+        FreightlinerContracts = "J9",
+        FreightlinerOther = "H9",
+        CoalDistributive = "A0",
+        CoalElectricity = "E0",
+        CoalOtherAndNuclear = "B0",
+        Metals = "B1",
+        Aggregates = "B4",
+        DomesticAndIndustrialWaste = "B5",
+        /// Building materials, trainload freight
+        TrainloadBuildingMaterials = "B6",
+        PetroleumProducts = "B7",
+        ChannelTunnelMixedBusiness = "H0",
+        ChannelTunnelIntermodal = "H1",
+        ChannelTunnelAutomotive = "H3",
+        ChannelTunnelContractServices = "H4",
+        ChannelTunnelHaulmark = "H5",
+        ChannelTunnelJointVenture = "H6",
+    }
+}
+
+impl TrainCategory {
+    /// Whether the category carries passengers, as an ordinary, express or
+    /// bus service
+    pub fn is_passenger(&self) -> bool {
+        matches!(
+            self,
+            Self::LondonUndergroundOrMetro
+                | Self::UnadvertisedOrdinaryPassenger
+                | Self::OrdinaryPassenger
+                | Self::StaffTrain
+                | Self::Mixed
+                | Self::ChannelTunnel
+                | Self::EuropeanSleeper
+                | Self::International
+                | Self::Motorail
+                | Self::UnadvertisedExpress
+                | Self::ExpressPassenger
+                | Self::DomesticSleeper
+                | Self::ReplacementBus
+                | Self::WttBus
+        )
+    }
+
+    /// Whether the category is a freight service, either trainload or
+    /// distribution
+    pub fn is_freight(&self) -> bool {
+        matches!(
+            self,
+            Self::AutomotiveComponents
+                | Self::AutomotiveVehicles
+                | Self::EdibleProducts
+                | Self::IndustrialMinerals
+                | Self::Chemicals
+                | Self::BuildingMaterials
+                | Self::GeneralMerchandise
+                | Self::European
+                | Self::FreightlinerContracts
+                | Self::FreightlinerOther
+                | Self::CoalDistributive
+                | Self::CoalElectricity
+                | Self::CoalOtherAndNuclear
+                | Self::Metals
+                | Self::Aggregates
+                | Self::DomesticAndIndustrialWaste
+                | Self::TrainloadBuildingMaterials
+                | Self::PetroleumProducts
+                | Self::ChannelTunnelMixedBusiness
+                | Self::ChannelTunnelIntermodal
+                | Self::ChannelTunnelAutomotive
+                | Self::ChannelTunnelContractServices
+                | Self::ChannelTunnelHaulmark
+                | Self::ChannelTunnelJointVenture
+        )
+    }
+}
+
+code_table! {
+    /// Type of traction the train is timed for
+    pub enum PowerType {
+        Diesel = "D",
+        DieselElectricMultipleUnit = "DEM",
+        DieselMechanicalMultipleUnit = "DMU",
+        Electric = "E",
+        ElectroDiesel = "ED",
+        /// Electric multiple unit plus a diesel, electric or electro-diesel
+        /// locomotive
+        ElectricMultipleUnitWithLocomotive = "EML",
+        ElectricMultipleUnit = "EMU",
+        HighSpeedTrain = "HST",
+    }
+}
+
+code_table! {
+    /// Diesel multiple unit timing load
+    pub enum DmuTimingLoad {
+        /// Class 14x 2-axle units
+        TwoAxle = "A",
+        /// Class 158, 168, 170 or 175 units
+        Class158 = "E",
+        /// Class 165/0 units
+        Class165Network = "N",
+        /// Class 150, 153, 155 or 156 units
+        Class150 = "S",
+        /// Class 165/1 or 166 units
+        Class165Thames = "T",
+        /// Class 220 or 221 units
+        Class220 = "V",
+        /// Class 159 units
+        Class159 = "X",
+    }
+}
+
+/// Load the train is timed to run with
+///
+/// The meaning of the field depends on the power type of the train.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TimingLoad {
+    /// Trailing load in tonnes, for locomotive hauled trains
+    Tonnes(u16),
+    /// Class of electric multiple unit the train is timed for, such as `390`
+    ElectricMultipleUnitClass(u16),
+    /// Electric multiple unit timed with accelerated timings (`AT`)
+    AcceleratedTimings,
+    DieselMultipleUnit(DmuTimingLoad),
+    /// A load which could not be interpreted for the power type
+    Unknown(String),
+}
+
+impl TimingLoad {
+    /// Interprets a timing load field for the given power type
+    pub fn new(power_type: Option<&PowerType>, field: &str) -> Option<Self> {
+        let field = field.trim();
+
+        if field.is_empty() {
+            return None;
+        }
+
+        Some(match (power_type, field) {
+            (Some(PowerType::Diesel | PowerType::Electric | PowerType::ElectroDiesel), tonnes) => {
+                tonnes
+                    .parse()
+                    .map(Self::Tonnes)
+                    .unwrap_or_else(|_| Self::Unknown(field.to_string()))
+            }
+            (Some(PowerType::ElectricMultipleUnit), "AT") => Self::AcceleratedTimings,
+            (Some(PowerType::ElectricMultipleUnit), class) => class
+                .parse()
+                .map(Self::ElectricMultipleUnitClass)
+                .unwrap_or_else(|_| Self::Unknown(field.to_string())),
+            (Some(PowerType::DieselMechanicalMultipleUnit), code) => match code.parse() {
+                Ok(DmuTimingLoad::Unknown(_)) | Err(_) => Self::Unknown(field.to_string()),
+                Ok(load) => Self::DieselMultipleUnit(load),
+            },
+            _ => Self::Unknown(field.to_string()),
+        })
+    }
+
+    /// The code used in CIF
+    pub fn code(&self) -> String {
+        match self {
+            Self::Tonnes(tonnes) => tonnes.to_string(),
+            Self::ElectricMultipleUnitClass(class) => class.to_string(),
+            Self::AcceleratedTimings => "AT".to_string(),
+            Self::DieselMultipleUnit(load) => load.code().to_string(),
+            Self::Unknown(code) => code.clone(),
+        }
+    }
+}
+
+impl fmt::Display for TimingLoad {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.code())
+    }
+}
+
+/// Writes a four character timing load field as it was laid out when read,
+/// or left aligned
+pub(crate) fn write_timing_load(
+    f: &mut fmt::Formatter<'_>,
+    source: &SourceLine,
+    range: Range<usize>,
+    power_type: Option<&PowerType>,
+    timing_load: Option<&TimingLoad>,
+) -> fmt::Result {
+    source.write_field(
+        f,
+        range,
+        &timing_load.cloned(),
+        |text| Some(TimingLoad::new(power_type, text)),
+        |f| write_optional(f, timing_load, 4),
+    )
+}
+
+/// Serializes as the code used in CIF, such as `390` or `AT`
+#[cfg(feature = "serde")]
+impl serde::Serialize for TimingLoad {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.code())
+    }
+}
+
+code_table! {
+    /// Operating characteristic of a train
+    pub enum OperatingCharacteristic {
+        VacuumBraked = "B",
+        /// Timed at 100 mph
+        TimedAt100Mph = "C",
+        /// Driver only operated (coaching stock trains)
+        DriverOnlyOperated = "D",
+        ConveysMark4Coaches = "E",
+        /// Trainman (guard) required
+        GuardRequired = "G",
+        /// Timed at 110 mph
+        TimedAt110Mph = "M",
+        PushPull = "P",
+        RunsAsRequired = "Q",
+        /// Air conditioned with public address system
+        AirConditionedWithPa = "R",
+        SteamHeated = "S",
+        RunsToTerminalsOrYardsAsRequired = "Y",
+        /// May convey traffic to SB1C gauge, and is not to be diverted from
+        /// its booked route without authority
+        Sb1cGauge = "Z",
+    }
+}
+
+/// Decodes an operating characteristics field, made up of up to six single
+/// character codes
+pub(crate) fn parse_operating_chars(field: &str) -> Vec<OperatingCharacteristic> {
+    field
+        .chars()
+        .filter(|c| *c != ' ')
+        .map(|c| c.to_string().parse().unwrap_or_else(|e| match e {}))
+        .collect()
+}
+
+#[test]
+fn parse_codes() {
+    assert_eq!(
+        "OO".parse::<TrainCategory>().unwrap(),
+        TrainCategory::OrdinaryPassenger
+    );
+    assert!(TrainCategory::ExpressPassenger.is_passenger());
+    assert!(TrainCategory::Aggregates.is_freight());
+    assert!(!TrainCategory::EmptyCoachingStock.is_freight());
+    assert!(!TrainCategory::Metals.is_passenger());
+    assert_eq!(
+        "Q9".parse::<TrainCategory>().unwrap(),
+        TrainCategory::Unknown("Q9".to_string())
+    );
+
+    let power_type: PowerType = "EMU".parse().unwrap();
+    assert_eq!(power_type, PowerType::ElectricMultipleUnit);
+    assert_eq!(
+        TimingLoad::new(Some(&power_type), "397 "),
+        Some(TimingLoad::ElectricMultipleUnitClass(397))
+    );
+    assert_eq!(
+        TimingLoad::new(Some(&PowerType::DieselMechanicalMultipleUnit), "E   "),
+        Some(TimingLoad::DieselMultipleUnit(DmuTimingLoad::Class158))
+    );
+    assert_eq!(
+        TimingLoad::new(Some(&PowerType::Diesel), "1400"),
+        Some(TimingLoad::Tonnes(1400))
+    );
+    assert_eq!(TimingLoad::new(Some(&power_type), "    "), None);
+
+    assert_eq!(
+        parse_operating_chars("DQ    "),
+        vec![
+            OperatingCharacteristic::DriverOnlyOperated,
+            OperatingCharacteristic::RunsAsRequired
+        ]
+    );
+}
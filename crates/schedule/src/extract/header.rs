@@ -1,16 +1,18 @@
-use chrono::NaiveDateTime;
+use chrono::{NaiveDate, NaiveDateTime};
 use std::{fmt, str::FromStr};
 use thiserror::Error;
 
 use crate::error::RecordParsingError;
 
+use super::write_field;
+
 #[derive(Error, Debug)]
 pub enum ExtractTypeError {
     #[error("Invalid character")]
     InvalidCharacter,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ExtractType {
     #[cfg_attr(feature = "serde", serde(rename = "U"))]
@@ -19,6 +21,16 @@ pub enum ExtractType {
     Full,
 }
 
+impl ExtractType {
+    /// The code used in CIF
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Update => "U",
+            Self::Full => "F",
+        }
+    }
+}
+
 impl FromStr for ExtractType {
     type Err = ExtractTypeError;
 
@@ -31,17 +43,78 @@ impl FromStr for ExtractType {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Error, Debug)]
+pub enum MainframeIdentityError {
+    #[error("Mainframe identity must be in the form TPS.Uxxxxxx.PDyymmdd")]
+    InvalidFormat,
+    #[error("Invalid extract date")]
+    InvalidDate,
+}
+
+/// Name of the file the extract was produced as, such as
+/// `TPS.UDFROC1.PD250228`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MainframeIdentity {
+    /// Identity of the user the extract was produced for, such as `DFROC1`
+    #[cfg_attr(feature = "serde", serde(serialize_with = "super::serialize_text"))]
+    pub user_identity: String,
+    pub extract_date: NaiveDate,
+}
+
+impl FromStr for MainframeIdentity {
+    type Err = MainframeIdentityError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (user_identity, extract_date) = s
+            .strip_prefix("TPS.U")
+            .and_then(|s| s.split_once(".PD"))
+            .ok_or(MainframeIdentityError::InvalidFormat)?;
+
+        if user_identity.len() != 6 || !user_identity.is_ascii() {
+            return Err(MainframeIdentityError::InvalidFormat);
+        }
+
+        Ok(MainframeIdentity {
+            user_identity: user_identity.to_string(),
+            extract_date: NaiveDate::parse_from_str(extract_date, "%y%m%d")
+                .map_err(|_| MainframeIdentityError::InvalidDate)?,
+        })
+    }
+}
+
+impl fmt::Display for MainframeIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "TPS.U{}.PD{}",
+            self.user_identity,
+            self.extract_date.format("%y%m%d")
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 /// Struct representing the header record type in a CIF
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Header {
-    pub file_mainframe_identity: String,
+    pub file_mainframe_identity: MainframeIdentity,
     pub datetime_of_extract: NaiveDateTime,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "super::serialize_text"))]
     pub current_file_ref: String,
     pub extract_type: ExtractType,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "super::serialize_text"))]
     pub last_file_ref: String,
-    pub version: String,
-    pub extract_start_date: String,
-    pub extract_end_date: String,
+    /// CIF software version, such as `A`
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "super::serialize_character")
+    )]
+    pub version: char,
+    /// First date covered by the extract
+    pub extract_start_date: NaiveDate,
+    /// Last date covered by the extract
+    pub extract_end_date: NaiveDate,
 }
 
 impl FromStr for Header {
@@ -64,27 +137,36 @@ impl FromStr for Header {
             _ => return Err(RecordParsingError::InvalidLength),
         };
 
-        let file_mainframe_identity = stripped[0..20].to_string();
+        let file_mainframe_identity = stripped[0..20].parse().map_err(|_| {
+            RecordParsingError::invalid_field("File Mainframe Identity", stripped, 0..20)
+        })?;
         let datetime_of_extract_field = &stripped[20..30];
         let current_file_ref = stripped[30..37].to_string();
         let last_file_ref = stripped[37..44].to_string();
         let extract_type_field = &stripped[44..45];
-        let version = stripped[45..46].to_string();
+        let version = stripped.as_bytes()[45] as char;
 
-        let extract_start_date = stripped[46..52].to_string();
-        let extract_end_date = stripped[52..58].to_string();
+        let extract_start_date =
+            NaiveDate::parse_from_str(&stripped[46..52], "%d%m%y").map_err(|_| {
+                RecordParsingError::invalid_field("User Extract Start Date", stripped, 46..52)
+            })?;
+        let extract_end_date =
+            NaiveDate::parse_from_str(&stripped[52..58], "%d%m%y").map_err(|_| {
+                RecordParsingError::invalid_field("User Extract End Date", stripped, 52..58)
+            })?;
 
         let datetime_of_extract =
-            NaiveDateTime::parse_from_str(datetime_of_extract_field, "%d%m%y%H%M").unwrap();
+            NaiveDateTime::parse_from_str(datetime_of_extract_field, "%d%m%y%H%M").map_err(
+                |_| RecordParsingError::invalid_field("Date of Extract", stripped, 20..30),
+            )?;
 
         Ok(Header {
             file_mainframe_identity,
             current_file_ref,
             datetime_of_extract,
             last_file_ref,
-            extract_type: ExtractType::from_str(extract_type_field).map_err(|_| {
-                RecordParsingError::InvalidField("Extract Type", extract_type_field.to_string())
-            })?,
+            extract_type: ExtractType::from_str(extract_type_field)
+                .map_err(|_| RecordParsingError::invalid_field("Extract Type", stripped, 44..45))?,
             version,
             extract_start_date,
             extract_end_date,
@@ -92,6 +174,26 @@ impl FromStr for Header {
     }
 }
 
+impl fmt::Display for Header {
+    /// Writes the record as a line of CIF
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("HD")?;
+        write_field(f, &self.file_mainframe_identity, 20)?;
+        write!(f, "{}", self.datetime_of_extract.format("%d%m%y%H%M"))?;
+        write_field(f, &self.current_file_ref, 7)?;
+        write_field(f, &self.last_file_ref, 7)?;
+        write!(f, "{}{}", self.extract_type.code(), self.version)?;
+        write!(
+            f,
+            "{}{}",
+            self.extract_start_date.format("%d%m%y"),
+            self.extract_end_date.format("%d%m%y")
+        )?;
+        write_field(f, "", 20)
+    }
+}
+
+#[cfg(feature = "serde")]
 impl<'de> serde::Deserialize<'de> for Header {
     fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
     where
@@ -104,3 +206,40 @@ impl<'de> serde::Deserialize<'de> for Header {
         Header::from_str(string).map_err(serde::de::Error::custom)
     }
 }
+
+#[test]
+fn deserialize_hd() -> Result<(), Box<dyn std::error::Error>> {
+    let raw = "HDTPS.UDFROC1.PD2502282802252154DFROC1B       FA280225280226                    ";
+
+    let header = Header::from_str(raw)?;
+
+    assert_eq!(header.file_mainframe_identity.user_identity, "DFROC1");
+    assert_eq!(
+        header.file_mainframe_identity.extract_date,
+        NaiveDate::from_ymd_opt(2025, 2, 28).unwrap()
+    );
+    assert_eq!(
+        header.file_mainframe_identity.to_string(),
+        "TPS.UDFROC1.PD250228"
+    );
+    assert_eq!(header.version, 'A');
+    assert_eq!(
+        header.extract_start_date,
+        NaiveDate::from_ymd_opt(2025, 2, 28).unwrap()
+    );
+    assert_eq!(
+        header.extract_end_date,
+        NaiveDate::from_ymd_opt(2026, 2, 28).unwrap()
+    );
+
+    let malformed = raw.replace("2802252154", "2802252199");
+    assert!(matches!(
+        Header::from_str(&malformed),
+        Err(RecordParsingError::InvalidField {
+            field: "Date of Extract",
+            ..
+        })
+    ));
+
+    Ok(())
+}
@@ -2,8 +2,11 @@ use std::{ops::Deref, str::FromStr};
 
 use thiserror::Error;
 
-#[derive(Debug, Clone)]
-pub struct Crs(String);
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Crs(
+    #[cfg_attr(feature = "serde", serde(serialize_with = "super::serialize_text"))] String,
+);
 
 impl Deref for Crs {
     type Target = str;
@@ -37,8 +40,55 @@ impl FromStr for Crs {
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct Atoc(String);
+/// Two character code identifying the train operating company
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Atoc(
+    #[cfg_attr(feature = "serde", serde(serialize_with = "super::serialize_text"))] String,
+);
+
+#[derive(Debug, Error)]
+pub enum AtocParsingError {
+    #[error("Atoc code must be 2 characters")]
+    InvalidLength,
+    #[error("Atoc code must not contain any non ascii characters")]
+    NonAsciiCharacters,
+}
+
+impl FromStr for Atoc {
+    type Err = AtocParsingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.is_ascii() {
+            return Err(AtocParsingError::NonAsciiCharacters);
+        }
+
+        if s.len() != 2 {
+            return Err(AtocParsingError::InvalidLength);
+        }
+
+        Ok(Atoc(s.to_string()))
+    }
+}
+
+impl Deref for Atoc {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_str()
+    }
+}
 
 #[derive(Debug)]
-pub struct TrainUid(String);
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TrainUid(
+    #[cfg_attr(feature = "serde", serde(serialize_with = "super::serialize_text"))] String,
+);
+
+impl Deref for TrainUid {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_str()
+    }
+}
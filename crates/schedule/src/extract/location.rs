@@ -1,16 +1,22 @@
-use std::{ops::Deref, str::FromStr};
+use std::{fmt, ops::Deref, str::FromStr};
 
-use serde::{
-    de::{self, Visitor},
-    Deserialize,
-};
 use thiserror::Error;
 
 use crate::error::RecordParsingError;
 
+use super::{
+    activity::{write_activities, Activities},
+    parse_optional, parse_suffix, parse_text,
+    time::{parse_public_time, write_allowance, write_public_time, Allowance, ScheduledTime},
+    write_field, write_optional, SourceLine,
+};
+
 /// National Location Code
-#[derive(Debug, Clone)]
-pub struct Nalco(String);
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Nalco(
+    #[cfg_attr(feature = "serde", serde(serialize_with = "super::serialize_text"))] String,
+);
 
 #[derive(Debug, Error)]
 pub enum NalcoParsingError {
@@ -49,8 +55,13 @@ impl Deref for Nalco {
 }
 
 /// Representation of a TIPLOC (Timing Point Location Code)
-#[derive(Debug, Clone)]
-pub struct Tiploc(String);
+///
+/// The space padding used in fixed width records is not kept.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Tiploc(
+    #[cfg_attr(feature = "serde", serde(serialize_with = "super::serialize_text"))] String,
+);
 
 impl Deref for Tiploc {
     type Target = str;
@@ -60,6 +71,12 @@ impl Deref for Tiploc {
     }
 }
 
+impl fmt::Display for Tiploc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum TiplocParsingError {
     #[error("Tiploc must not be longer than 7 characters")]
@@ -72,13 +89,17 @@ impl FromStr for Tiploc {
     type Err = TiplocParsingError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.is_ascii() {
+            return Err(TiplocParsingError::NonAsciiCharacters);
+        }
+
         if s.len() > 7 {
             return Err(TiplocParsingError::InvalidLength);
         }
 
         let mut string = String::with_capacity(7);
 
-        string.push_str(s);
+        string.push_str(s.trim_end());
 
         Ok(Self(string))
     }
@@ -87,7 +108,7 @@ impl FromStr for Tiploc {
 /// Stanox
 ///
 /// TOPS location code
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Stanox(String);
 
 impl Deref for Stanox {
@@ -127,11 +148,25 @@ impl Stanox {
     }
 }
 
-impl<'de> Deserialize<'de> for Stanox {
+#[cfg(feature = "serde")]
+impl serde::Serialize for Stanox {
+    /// Serializes the code, or `null` when there isn't one
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.is_empty() {
+            true => serializer.serialize_none(),
+            false => super::serialize_text(&self.0, serializer),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Stanox {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
-        D: de::Deserializer<'de>,
+        D: serde::Deserializer<'de>,
     {
+        use serde::de::{self, Visitor};
+
         struct StanoxVisitor;
 
         impl<'de> Visitor<'de> for StanoxVisitor {
@@ -162,15 +197,34 @@ pub enum PoMcpCodeParsingError {
 }
 
 /// Post Office Location Code (Unused)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PoMcpCode(String);
 
+impl Deref for PoMcpCode {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_str()
+    }
+}
+
 impl PoMcpCode {
-    fn is_empty(&self) -> bool {
+    pub fn is_empty(&self) -> bool {
         self.0 == "0000" || self.0 == "   0"
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for PoMcpCode {
+    /// Serializes the code, or `null` when there isn't one
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.is_empty() {
+            true => serializer.serialize_none(),
+            false => super::serialize_text(&self.0, serializer),
+        }
+    }
+}
+
 impl FromStr for PoMcpCode {
     type Err = PoMcpCodeParsingError;
 
@@ -187,10 +241,22 @@ impl FromStr for PoMcpCode {
     }
 }
 
-#[derive(Debug)]
+/// Origin Location Record
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct OriginLocation {
-    // location: Tiploc,
-    scheduled_departure_time: String,
+    pub location: Tiploc,
+    pub suffix: Option<char>,
+    pub scheduled_departure_time: ScheduledTime,
+    pub public_departure_time: Option<ScheduledTime>,
+    pub platform: Option<String>,
+    pub line: Option<String>,
+    pub engineering_allowance: Option<Allowance>,
+    pub pathing_allowance: Option<Allowance>,
+    pub activity: Activities,
+    pub performance_allowance: Option<Allowance>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) source: SourceLine,
 }
 
 impl FromStr for OriginLocation {
@@ -213,28 +279,93 @@ impl FromStr for OriginLocation {
         };
 
         Ok(Self {
-            // location: Tiploc::from_str(&stripped[0..8])
-            //     .map_err(|e| RecordParsingError::InvalidField("location", e.to_string()))?,
-            scheduled_departure_time: stripped[8..13].to_string(),
+            location: Tiploc::from_str(&stripped[0..7])
+                .map_err(|_| RecordParsingError::invalid_field("Location", stripped, 0..7))?,
+            suffix: parse_suffix(&stripped[7..8]),
+            scheduled_departure_time: stripped[8..13].parse().map_err(|_| {
+                RecordParsingError::invalid_field("Scheduled Departure", stripped, 8..13)
+            })?,
+            public_departure_time: parse_public_time(&stripped[13..17]).map_err(|_| {
+                RecordParsingError::invalid_field("Public Departure", stripped, 13..17)
+            })?,
+            platform: parse_text(&stripped[17..20]),
+            line: parse_text(&stripped[20..23]),
+            engineering_allowance: parse_optional(&stripped[23..25]).map_err(|_| {
+                RecordParsingError::invalid_field("Engineering Allowance", stripped, 23..25)
+            })?,
+            pathing_allowance: parse_optional(&stripped[25..27]).map_err(|_| {
+                RecordParsingError::invalid_field("Pathing Allowance", stripped, 25..27)
+            })?,
+            activity: Activities::from_str(&stripped[27..39]).unwrap_or_else(|e| match e {}),
+            performance_allowance: parse_optional(&stripped[39..41]).map_err(|_| {
+                RecordParsingError::invalid_field("Performance Allowance", stripped, 39..41)
+            })?,
+            source: SourceLine::new(stripped),
         })
     }
 }
 
-impl<'de> Deserialize<'de> for OriginLocation {
+impl fmt::Display for OriginLocation {
+    /// Writes the record as a line of CIF
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("LO")?;
+        write_field(f, &self.location, 7)?;
+        write_optional(f, self.suffix, 1)?;
+        write_field(f, self.scheduled_departure_time, 5)?;
+        write_public_time(f, &self.source, 13..17, self.public_departure_time)?;
+        write_optional(f, self.platform.as_ref(), 3)?;
+        write_optional(f, self.line.as_ref(), 3)?;
+        write_allowance(f, &self.source, 23..25, self.engineering_allowance)?;
+        write_allowance(f, &self.source, 25..27, self.pathing_allowance)?;
+        write_activities(f, &self.source, 27..39, &self.activity)?;
+        write_allowance(f, &self.source, 39..41, self.performance_allowance)?;
+        write_field(f, "", 37)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for OriginLocation {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
-        D: de::Deserializer<'de>,
+        D: serde::Deserializer<'de>,
     {
-        Self::from_str(Deserialize::deserialize(deserializer)?).map_err(de::Error::custom)
+        Self::from_str(serde::Deserialize::deserialize(deserializer)?)
+            .map_err(serde::de::Error::custom)
     }
 }
 
-#[derive(Debug)]
-pub struct IntermediateLocation;
+/// Intermediate Location Record
+///
+/// A train either calls at the location, with an arrival and departure time,
+/// or passes it, with only a pass time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct IntermediateLocation {
+    pub location: Tiploc,
+    pub suffix: Option<char>,
+    pub scheduled_arrival_time: Option<ScheduledTime>,
+    pub scheduled_departure_time: Option<ScheduledTime>,
+    pub scheduled_pass_time: Option<ScheduledTime>,
+    pub public_arrival_time: Option<ScheduledTime>,
+    pub public_departure_time: Option<ScheduledTime>,
+    pub platform: Option<String>,
+    pub line: Option<String>,
+    pub path: Option<String>,
+    pub activity: Activities,
+    pub engineering_allowance: Option<Allowance>,
+    pub pathing_allowance: Option<Allowance>,
+    pub performance_allowance: Option<Allowance>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) source: SourceLine,
+}
 
 impl FromStr for IntermediateLocation {
     type Err = RecordParsingError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.is_ascii() {
+            return Err(RecordParsingError::NonAscii);
+        }
+
         let stripped = match s.len() {
             78 => s,
             80 => {
@@ -247,28 +378,179 @@ impl FromStr for IntermediateLocation {
             _ => return Err(RecordParsingError::InvalidLength),
         };
 
-        Ok(IntermediateLocation)
+        Ok(IntermediateLocation {
+            location: Tiploc::from_str(&stripped[0..7])
+                .map_err(|_| RecordParsingError::invalid_field("Location", stripped, 0..7))?,
+            suffix: parse_suffix(&stripped[7..8]),
+            scheduled_arrival_time: parse_optional(&stripped[8..13]).map_err(|_| {
+                RecordParsingError::invalid_field("Scheduled Arrival", stripped, 8..13)
+            })?,
+            scheduled_departure_time: parse_optional(&stripped[13..18]).map_err(|_| {
+                RecordParsingError::invalid_field("Scheduled Departure", stripped, 13..18)
+            })?,
+            scheduled_pass_time: parse_optional(&stripped[18..23]).map_err(|_| {
+                RecordParsingError::invalid_field("Scheduled Pass", stripped, 18..23)
+            })?,
+            public_arrival_time: parse_public_time(&stripped[23..27]).map_err(|_| {
+                RecordParsingError::invalid_field("Public Arrival", stripped, 23..27)
+            })?,
+            public_departure_time: parse_public_time(&stripped[27..31]).map_err(|_| {
+                RecordParsingError::invalid_field("Public Departure", stripped, 27..31)
+            })?,
+            platform: parse_text(&stripped[31..34]),
+            line: parse_text(&stripped[34..37]),
+            path: parse_text(&stripped[37..40]),
+            activity: Activities::from_str(&stripped[40..52]).unwrap_or_else(|e| match e {}),
+            engineering_allowance: parse_optional(&stripped[52..54]).map_err(|_| {
+                RecordParsingError::invalid_field("Engineering Allowance", stripped, 52..54)
+            })?,
+            pathing_allowance: parse_optional(&stripped[54..56]).map_err(|_| {
+                RecordParsingError::invalid_field("Pathing Allowance", stripped, 54..56)
+            })?,
+            performance_allowance: parse_optional(&stripped[56..58]).map_err(|_| {
+                RecordParsingError::invalid_field("Performance Allowance", stripped, 56..58)
+            })?,
+            source: SourceLine::new(stripped),
+        })
     }
 }
 
-impl<'de> Deserialize<'de> for IntermediateLocation {
+impl fmt::Display for IntermediateLocation {
+    /// Writes the record as a line of CIF
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("LI")?;
+        write_field(f, &self.location, 7)?;
+        write_optional(f, self.suffix, 1)?;
+        write_optional(f, self.scheduled_arrival_time, 5)?;
+        write_optional(f, self.scheduled_departure_time, 5)?;
+        write_optional(f, self.scheduled_pass_time, 5)?;
+        write_public_time(f, &self.source, 23..27, self.public_arrival_time)?;
+        write_public_time(f, &self.source, 27..31, self.public_departure_time)?;
+        write_optional(f, self.platform.as_ref(), 3)?;
+        write_optional(f, self.line.as_ref(), 3)?;
+        write_optional(f, self.path.as_ref(), 3)?;
+        write_activities(f, &self.source, 40..52, &self.activity)?;
+        write_allowance(f, &self.source, 52..54, self.engineering_allowance)?;
+        write_allowance(f, &self.source, 54..56, self.pathing_allowance)?;
+        write_allowance(f, &self.source, 56..58, self.performance_allowance)?;
+        write_field(f, "", 20)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for IntermediateLocation {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
-        D: de::Deserializer<'de>,
+        D: serde::Deserializer<'de>,
     {
-        Self::from_str(Deserialize::deserialize(deserializer)?).map_err(de::Error::custom)
+        Self::from_str(serde::Deserialize::deserialize(deserializer)?)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Terminating Location Record
+///
+/// Allowances are given against the location they are taken after, so the
+/// terminating location never has any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TerminatingLocation {
+    pub location: Tiploc,
+    pub suffix: Option<char>,
+    pub scheduled_arrival_time: ScheduledTime,
+    pub public_arrival_time: Option<ScheduledTime>,
+    pub platform: Option<String>,
+    pub path: Option<String>,
+    pub activity: Activities,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) source: SourceLine,
+}
+
+impl FromStr for TerminatingLocation {
+    type Err = RecordParsingError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.is_ascii() {
+            return Err(RecordParsingError::NonAscii);
+        }
+
+        let stripped = match s.len() {
+            78 => s,
+            80 => {
+                if &s[0..2] != "LT" {
+                    return Err(RecordParsingError::UnexpectedRecordIdentity("LT"));
+                }
+
+                &s[2..]
+            }
+            _ => return Err(RecordParsingError::InvalidLength),
+        };
+
+        Ok(TerminatingLocation {
+            location: Tiploc::from_str(&stripped[0..7])
+                .map_err(|_| RecordParsingError::invalid_field("Location", stripped, 0..7))?,
+            suffix: parse_suffix(&stripped[7..8]),
+            scheduled_arrival_time: stripped[8..13].parse().map_err(|_| {
+                RecordParsingError::invalid_field("Scheduled Arrival", stripped, 8..13)
+            })?,
+            public_arrival_time: parse_public_time(&stripped[13..17]).map_err(|_| {
+                RecordParsingError::invalid_field("Public Arrival", stripped, 13..17)
+            })?,
+            platform: parse_text(&stripped[17..20]),
+            path: parse_text(&stripped[20..23]),
+            activity: Activities::from_str(&stripped[23..35]).unwrap_or_else(|e| match e {}),
+            source: SourceLine::new(stripped),
+        })
     }
 }
 
-#[derive(Debug)]
-pub struct TerminatingLocation;
+impl fmt::Display for TerminatingLocation {
+    /// Writes the record as a line of CIF
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("LT")?;
+        write_field(f, &self.location, 7)?;
+        write_optional(f, self.suffix, 1)?;
+        write_field(f, self.scheduled_arrival_time, 5)?;
+        write_public_time(f, &self.source, 13..17, self.public_arrival_time)?;
+        write_optional(f, self.platform.as_ref(), 3)?;
+        write_optional(f, self.path.as_ref(), 3)?;
+        write_activities(f, &self.source, 23..35, &self.activity)?;
+        write_field(f, "", 43)
+    }
+}
 
-impl<'de> Deserialize<'de> for TerminatingLocation {
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for TerminatingLocation {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
-        D: de::Deserializer<'de>,
+        D: serde::Deserializer<'de>,
     {
-        let _string: &str = Deserialize::deserialize(deserializer)?;
-        Ok(Self)
+        Self::from_str(serde::Deserialize::deserialize(deserializer)?)
+            .map_err(serde::de::Error::custom)
     }
 }
+
+#[test]
+fn deserialize_li() -> Result<(), Box<dyn std::error::Error>> {
+    let raw = "LIHAYMRKT 1932H1934      193300003  US    D              H                      ";
+
+    let location = IntermediateLocation::from_str(raw)?;
+
+    assert_eq!(&*location.location, "HAYMRKT");
+    assert_eq!(location.suffix, None);
+    assert_eq!(
+        location.scheduled_arrival_time,
+        ScheduledTime::new(19, 32, true)
+    );
+    assert_eq!(location.public_departure_time, None);
+    assert!(location.activity.sets_down());
+    assert!(!location.activity.picks_up());
+    assert_eq!(location.scheduled_pass_time, None);
+    assert_eq!(location.platform.as_deref(), Some("3"));
+    assert_eq!(location.line.as_deref(), Some("US"));
+    assert_eq!(
+        location.pathing_allowance,
+        Some(Allowance::from_half_minutes(1))
+    );
+
+    Ok(())
+}
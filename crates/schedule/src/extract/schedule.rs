@@ -1,53 +1,462 @@
-use std::{fmt::Display, str::FromStr};
+use std::{fmt, str::FromStr};
 
-use chrono::{NaiveDate, Weekday};
-use serde::{
-    de::{IntoDeserializer, Visitor},
-    Deserialize,
-};
+use chrono::{Datelike, NaiveDate, Weekday};
 
-use crate::error::RecordParsingError;
+use crate::error::{InvalidCode, RecordParsingError};
 
-use super::{identifier::Atoc, TransactionType};
+use super::{
+    characteristics::{
+        parse_operating_chars, write_timing_load, OperatingCharacteristic, PowerType, TimingLoad,
+        TrainCategory,
+    },
+    identifier::Atoc,
+    location::Tiploc,
+    parse_days, parse_optional, parse_suffix, parse_text, write_date, write_days, write_end_date,
+    write_field, write_optional, SourceLine, TransactionType,
+};
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum StpIndicator {
-    #[serde(rename = "C")]
+    #[cfg_attr(feature = "serde", serde(rename = "C"))]
     Cancellation,
-    #[serde(rename = "N")]
+    #[cfg_attr(feature = "serde", serde(rename = "N"))]
     New,
-    #[serde(rename = "O")]
+    #[cfg_attr(feature = "serde", serde(rename = "O"))]
     Overlay,
-    #[serde(rename = "P")]
+    #[cfg_attr(feature = "serde", serde(rename = "P"))]
     Permanent,
 }
 
-#[derive(Debug, Clone)]
+impl StpIndicator {
+    /// Precedence over other schedules for the same train on the same date,
+    /// where higher wins
+    ///
+    /// Cancellations take precedence over new schedules and overlays, which
+    /// take precedence over permanent schedules.
+    pub fn precedence(&self) -> u8 {
+        match self {
+            Self::Cancellation => 2,
+            Self::New | Self::Overlay => 1,
+            Self::Permanent => 0,
+        }
+    }
+
+    /// The code used in CIF
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Cancellation => "C",
+            Self::New => "N",
+            Self::Overlay => "O",
+            Self::Permanent => "P",
+        }
+    }
+}
+
+impl FromStr for StpIndicator {
+    type Err = InvalidCode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "C" => Self::Cancellation,
+            "N" => Self::New,
+            "O" => Self::Overlay,
+            "P" => Self::Permanent,
+            _ => return Err(InvalidCode(s.to_string())),
+        })
+    }
+}
+
+/// Bank holidays on which the train does not run
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BankHolidayRunning {
+    /// Does not run on specified Bank Holiday Mondays (`X`)
+    #[cfg_attr(feature = "serde", serde(rename = "X"))]
+    NotOnBankHolidayMondays,
+    /// Does not run on specified Edinburgh Holiday dates (`E`)
+    #[cfg_attr(feature = "serde", serde(rename = "E"))]
+    NotOnEdinburghHolidays,
+    /// Does not run on specified Glasgow Holiday dates (`G`)
+    #[cfg_attr(feature = "serde", serde(rename = "G"))]
+    NotOnGlasgowHolidays,
+}
+
+impl BankHolidayRunning {
+    /// The code used in CIF
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NotOnBankHolidayMondays => "X",
+            Self::NotOnEdinburghHolidays => "E",
+            Self::NotOnGlasgowHolidays => "G",
+        }
+    }
+}
+
+impl FromStr for BankHolidayRunning {
+    type Err = InvalidCode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "X" => Self::NotOnBankHolidayMondays,
+            "E" => Self::NotOnEdinburghHolidays,
+            "G" => Self::NotOnGlasgowHolidays,
+            _ => return Err(InvalidCode(s.to_string())),
+        })
+    }
+}
+
+/// Source of the holiday dates referred to by [BankHolidayRunning]
+///
+/// CIF doesn't carry the holiday dates themselves, so they are supplied by
+/// the caller when working out which dates a schedule runs on. Closures taking
+/// the date and the indicator can be used as a calendar.
+pub trait HolidayCalendar {
+    /// Whether the date is one of the holidays the indicator refers to
+    fn is_holiday(&self, date: NaiveDate, holiday: &BankHolidayRunning) -> bool;
+}
+
+/// A calendar without any holidays
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoHolidays;
+
+impl HolidayCalendar for NoHolidays {
+    fn is_holiday(&self, _date: NaiveDate, _holiday: &BankHolidayRunning) -> bool {
+        false
+    }
+}
+
+impl<F> HolidayCalendar for F
+where
+    F: Fn(NaiveDate, &BankHolidayRunning) -> bool,
+{
+    fn is_holiday(&self, date: NaiveDate, holiday: &BankHolidayRunning) -> bool {
+        self(date, holiday)
+    }
+}
+
+/// The type of service and whether it is permanent or short term planned
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TrainStatus {
+    #[cfg_attr(feature = "serde", serde(rename = "B"))]
+    Bus,
+    #[cfg_attr(feature = "serde", serde(rename = "F"))]
+    Freight,
+    #[cfg_attr(feature = "serde", serde(rename = "P"))]
+    PassengerAndParcels,
+    #[cfg_attr(feature = "serde", serde(rename = "S"))]
+    Ship,
+    #[cfg_attr(feature = "serde", serde(rename = "T"))]
+    Trip,
+    #[cfg_attr(feature = "serde", serde(rename = "1"))]
+    StpPassengerAndParcels,
+    #[cfg_attr(feature = "serde", serde(rename = "2"))]
+    StpFreight,
+    #[cfg_attr(feature = "serde", serde(rename = "3"))]
+    StpTrip,
+    #[cfg_attr(feature = "serde", serde(rename = "4"))]
+    StpShip,
+    #[cfg_attr(feature = "serde", serde(rename = "5"))]
+    StpBus,
+}
+
+impl TrainStatus {
+    /// The code used in CIF
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Bus => "B",
+            Self::Freight => "F",
+            Self::PassengerAndParcels => "P",
+            Self::Ship => "S",
+            Self::Trip => "T",
+            Self::StpPassengerAndParcels => "1",
+            Self::StpFreight => "2",
+            Self::StpTrip => "3",
+            Self::StpShip => "4",
+            Self::StpBus => "5",
+        }
+    }
+}
+
+impl FromStr for TrainStatus {
+    type Err = InvalidCode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "B" => Self::Bus,
+            "F" => Self::Freight,
+            "P" => Self::PassengerAndParcels,
+            "S" => Self::Ship,
+            "T" => Self::Trip,
+            "1" => Self::StpPassengerAndParcels,
+            "2" => Self::StpFreight,
+            "3" => Self::StpTrip,
+            "4" => Self::StpShip,
+            "5" => Self::StpBus,
+            _ => return Err(InvalidCode(s.to_string())),
+        })
+    }
+}
+
+/// Classes of seating available on the train
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SeatingClass {
+    /// First and Standard class (`B`)
+    ///
+    /// A blank seating class, which on passenger services also means both, is
+    /// read as `None`.
+    #[cfg_attr(feature = "serde", serde(rename = "B"))]
+    FirstAndStandard,
+    /// Standard class only (`S`)
+    #[cfg_attr(feature = "serde", serde(rename = "S"))]
+    StandardOnly,
+}
+
+impl SeatingClass {
+    /// The code used in CIF
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::FirstAndStandard => "B",
+            Self::StandardOnly => "S",
+        }
+    }
+}
+
+impl FromStr for SeatingClass {
+    type Err = InvalidCode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "B" => Self::FirstAndStandard,
+            "S" => Self::StandardOnly,
+            _ => return Err(InvalidCode(s.to_string())),
+        })
+    }
+}
+
+/// Classes of sleeping accommodation available on the train
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Sleepers {
+    #[cfg_attr(feature = "serde", serde(rename = "B"))]
+    FirstAndStandard,
+    #[cfg_attr(feature = "serde", serde(rename = "F"))]
+    FirstOnly,
+    #[cfg_attr(feature = "serde", serde(rename = "S"))]
+    StandardOnly,
+}
+
+impl Sleepers {
+    /// The code used in CIF
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::FirstAndStandard => "B",
+            Self::FirstOnly => "F",
+            Self::StandardOnly => "S",
+        }
+    }
+}
+
+impl FromStr for Sleepers {
+    type Err = InvalidCode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "B" => Self::FirstAndStandard,
+            "F" => Self::FirstOnly,
+            "S" => Self::StandardOnly,
+            _ => return Err(InvalidCode(s.to_string())),
+        })
+    }
+}
+
+/// Seat reservation policy for the train
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Reservations {
+    /// Seat reservations compulsory (`A`)
+    #[cfg_attr(feature = "serde", serde(rename = "A"))]
+    Compulsory,
+    /// Reservations for bicycles essential (`E`)
+    #[cfg_attr(feature = "serde", serde(rename = "E"))]
+    BicyclesEssential,
+    /// Seat reservations recommended (`R`)
+    #[cfg_attr(feature = "serde", serde(rename = "R"))]
+    Recommended,
+    /// Seat reservations possible from any station (`S`)
+    #[cfg_attr(feature = "serde", serde(rename = "S"))]
+    Possible,
+}
+
+impl Reservations {
+    /// The code used in CIF
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Compulsory => "A",
+            Self::BicyclesEssential => "E",
+            Self::Recommended => "R",
+            Self::Possible => "S",
+        }
+    }
+}
+
+impl FromStr for Reservations {
+    type Err = InvalidCode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "A" => Self::Compulsory,
+            "E" => Self::BicyclesEssential,
+            "R" => Self::Recommended,
+            "S" => Self::Possible,
+            _ => return Err(InvalidCode(s.to_string())),
+        })
+    }
+}
+
+/// Catering facilities available on the train
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Catering {
+    /// Buffet service (`C`)
+    #[cfg_attr(feature = "serde", serde(rename = "C"))]
+    Buffet,
+    /// Restaurant car available for First Class passengers (`F`)
+    #[cfg_attr(feature = "serde", serde(rename = "F"))]
+    FirstClassRestaurant,
+    /// Hot food available (`H`)
+    #[cfg_attr(feature = "serde", serde(rename = "H"))]
+    HotFood,
+    /// Meal included for First Class passengers (`M`)
+    #[cfg_attr(feature = "serde", serde(rename = "M"))]
+    FirstClassMeal,
+    /// Wheelchair only reservations (`P`)
+    #[cfg_attr(feature = "serde", serde(rename = "P"))]
+    WheelchairReservations,
+    /// Restaurant (`R`)
+    #[cfg_attr(feature = "serde", serde(rename = "R"))]
+    Restaurant,
+    /// Trolley service (`T`)
+    #[cfg_attr(feature = "serde", serde(rename = "T"))]
+    Trolley,
+}
+
+impl Catering {
+    /// The code used in CIF
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Buffet => "C",
+            Self::FirstClassRestaurant => "F",
+            Self::HotFood => "H",
+            Self::FirstClassMeal => "M",
+            Self::WheelchairReservations => "P",
+            Self::Restaurant => "R",
+            Self::Trolley => "T",
+        }
+    }
+}
+
+impl FromStr for Catering {
+    type Err = InvalidCode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "C" => Self::Buffet,
+            "F" => Self::FirstClassRestaurant,
+            "H" => Self::HotFood,
+            "M" => Self::FirstClassMeal,
+            "P" => Self::WheelchairReservations,
+            "R" => Self::Restaurant,
+            "T" => Self::Trolley,
+            _ => return Err(InvalidCode(s.to_string())),
+        })
+    }
+}
+
+/// Decodes a catering field made up of up to four single character codes
+pub(crate) fn parse_catering(field: &str) -> Result<Vec<Catering>, InvalidCode> {
+    field
+        .chars()
+        .filter(|c| *c != ' ')
+        .map(|c| c.to_string().parse())
+        .collect()
+}
+
+/// Joins single character codes into one field, the reverse of
+/// [parse_catering] and [parse_operating_chars]
+fn codes<T>(values: &[T], code: fn(&T) -> &str) -> String {
+    values.iter().map(code).collect()
+}
+
+/// Basic Schedule Record
+///
+/// Most fields are blank on STP cancellations and deletions, so any field
+/// which may be absent is optional.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct BasicSchedule {
     pub transaction_type: TransactionType,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "super::serialize_text"))]
     pub train_uid: String,
     pub from: NaiveDate,
+    /// Last date the schedule runs on, which deletions may leave blank, in
+    /// which case it is the same as `from`
     pub to: NaiveDate,
     pub days: Vec<Weekday>,
-    // pub bank_holiday_running: bool,
-    // pub train_status: String,
-    // pub train_category: String,
-    // pub train_identity: String,
-    // pub headcode: String,
-    // pub course_indicator: String,
-    // pub pcc_tsc: String,
-    // pub business_sector: String,
-    // pub power_type: String,
-    // pub timing_load: String,
-    // pub speed: String,
-    // pub operating_chars: String,
-    // pub train_class: String,
-    // pub sleepers: String,
-    // pub reservations: bool,
-    // pub connector_indicator: bool,
-    // pub catering_code: String,
-    // pub service_branding: String,
+    pub bank_holiday_running: Option<BankHolidayRunning>,
+    pub train_status: Option<TrainStatus>,
+    pub train_category: Option<TrainCategory>,
+    /// Signalling ID, such as `1S75`
+    pub train_identity: Option<String>,
+    pub headcode: Option<String>,
+    pub course_indicator: Option<u8>,
+    /// Train Service Code, used to identify the service group for
+    /// performance monitoring
+    pub train_service_code: Option<String>,
+    pub portion_id: Option<char>,
+    pub power_type: Option<PowerType>,
+    pub timing_load: Option<TimingLoad>,
+    /// Maximum speed in miles per hour
+    pub speed: Option<u16>,
+    pub operating_chars: Vec<OperatingCharacteristic>,
+    pub seating_class: Option<SeatingClass>,
+    pub sleepers: Option<Sleepers>,
+    pub reservations: Option<Reservations>,
+    pub connection_indicator: Option<char>,
+    pub catering: Vec<Catering>,
+    pub service_branding: Option<String>,
     pub stp_indicator: StpIndicator,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) source: SourceLine,
+}
+
+impl BasicSchedule {
+    /// Whether the schedule runs on the date
+    ///
+    /// The date must be within the validity of the schedule, on one of its
+    /// days of the week and not a holiday it is excluded from.
+    pub fn runs_on(&self, date: NaiveDate, calendar: &impl HolidayCalendar) -> bool {
+        self.from <= date
+            && date <= self.to
+            && self.days.contains(&date.weekday())
+            && !self
+                .bank_holiday_running
+                .as_ref()
+                .is_some_and(|holiday| calendar.is_holiday(date, holiday))
+    }
+
+    /// Dates the schedule runs on, in order
+    pub fn running_dates<'a, C: HolidayCalendar>(
+        &'a self,
+        calendar: &'a C,
+    ) -> impl Iterator<Item = NaiveDate> + 'a {
+        self.from
+            .iter_days()
+            .take_while(|date| *date <= self.to)
+            .filter(|date| self.runs_on(*date, calendar))
+    }
 }
 
 impl FromStr for BasicSchedule {
@@ -72,30 +481,22 @@ impl FromStr for BasicSchedule {
 
         let train_uid = stripped[1..7].to_string();
 
-        let from = NaiveDate::parse_from_str(&stripped[7..13], "%y%m%d").map_err(|_| {
-            RecordParsingError::InvalidField("Date Runs From", stripped[7..13].to_string())
-        })?;
-
-        let to = NaiveDate::parse_from_str(&stripped[13..19], "%y%m%d").map_err(|_| {
-            RecordParsingError::InvalidField("Date Runs To", stripped[13..19].to_string())
-        })?;
-
-        let transaction_type = TransactionType::deserialize(stripped[0..1].into_deserializer())
-            .map_err(|_: serde::de::value::Error| {
-                RecordParsingError::InvalidField("Transaction Type", stripped[0..1].to_string())
-            })?;
-
-        let days: Vec<Weekday> = stripped[19..26]
-            .chars()
-            .enumerate()
-            .filter_map(|(i, value)| {
-                if value == '0' {
-                    return None;
-                } else {
-                    return Weekday::try_from(i as u8).ok();
-                }
-            })
-            .collect();
+        let from = NaiveDate::parse_from_str(&stripped[7..13], "%y%m%d")
+            .map_err(|_| RecordParsingError::invalid_field("Date Runs From", stripped, 7..13))?;
+
+        let transaction_type: TransactionType = stripped[0..1]
+            .parse()
+            .map_err(|_| RecordParsingError::invalid_field("Transaction Type", stripped, 0..1))?;
+
+        let to = match &stripped[13..19] {
+            "      " if transaction_type == TransactionType::Delete => from,
+            field => NaiveDate::parse_from_str(field, "%y%m%d")
+                .map_err(|_| RecordParsingError::invalid_field("Date Runs To", stripped, 13..19))?,
+        };
+
+        let days = parse_days(&stripped[19..26]);
+
+        let power_type: Option<PowerType> = parse_optional(&stripped[48..51]).unwrap_or_default();
 
         Ok(BasicSchedule {
             transaction_type,
@@ -103,60 +504,409 @@ impl FromStr for BasicSchedule {
             from,
             to,
             days,
-            // bank_holiday_running: todo!(),
-            // train_status: todo!(),
-            // train_category: todo!(),
-            // train_identity: todo!(),
-            // headcode: todo!(),
-            // course_indicator: todo!(),
-            // pcc_tsc: todo!(),
-            // business_sector: todo!(),
-            // power_type: todo!(),
-            // timing_load: todo!(),
-            // speed: todo!(),
-            // operating_chars: todo!(),
-            // train_class: todo!(),
-            // sleepers: todo!(),
-            // reservations: todo!(),
-            // connector_indicator: todo!(),
-            // catering_code: v[68..72].to_string(),
-            // service_branding: v[72..76].to_string(),
-            stp_indicator: StpIndicator::deserialize(stripped[77..].into_deserializer()).map_err(
-                |_: serde::de::value::Error| {
-                    RecordParsingError::InvalidField("STP Indicator", stripped[77..].to_string())
-                },
+            bank_holiday_running: parse_optional(&stripped[26..27]).map_err(|_| {
+                RecordParsingError::invalid_field("Bank Holiday Running", stripped, 26..27)
+            })?,
+            train_status: parse_optional(&stripped[27..28])
+                .map_err(|_| RecordParsingError::invalid_field("Train Status", stripped, 27..28))?,
+            train_category: parse_optional(&stripped[28..30]).unwrap_or_default(),
+            train_identity: parse_text(&stripped[30..34]),
+            headcode: parse_text(&stripped[34..38]),
+            course_indicator: parse_optional(&stripped[38..39]).map_err(|_| {
+                RecordParsingError::invalid_field("Course Indicator", stripped, 38..39)
+            })?,
+            train_service_code: parse_text(&stripped[39..47]),
+            portion_id: parse_optional(&stripped[47..48])
+                .map_err(|_| RecordParsingError::invalid_field("Portion Id", stripped, 47..48))?,
+            timing_load: TimingLoad::new(power_type.as_ref(), &stripped[51..55]),
+            power_type,
+            speed: parse_optional(stripped[55..58].trim())
+                .map_err(|_| RecordParsingError::invalid_field("Speed", stripped, 55..58))?,
+            operating_chars: parse_operating_chars(&stripped[58..64]),
+            seating_class: parse_optional(&stripped[64..65]).map_err(|_| {
+                RecordParsingError::invalid_field("Seating Class", stripped, 64..65)
+            })?,
+            sleepers: parse_optional(&stripped[65..66])
+                .map_err(|_| RecordParsingError::invalid_field("Sleepers", stripped, 65..66))?,
+            reservations: parse_optional(&stripped[66..67])
+                .map_err(|_| RecordParsingError::invalid_field("Reservations", stripped, 66..67))?,
+            connection_indicator: parse_optional(&stripped[67..68]).map_err(|_| {
+                RecordParsingError::invalid_field("Connection Indicator", stripped, 67..68)
+            })?,
+            catering: parse_catering(&stripped[68..72]).map_err(|_| {
+                RecordParsingError::invalid_field("Catering Code", stripped, 68..72)
+            })?,
+            service_branding: parse_text(&stripped[72..76]),
+            stp_indicator: stripped[77..].parse().map_err(|_| {
+                RecordParsingError::invalid_field("STP Indicator", stripped, 77..78)
+            })?,
+            source: SourceLine::new(stripped),
+        })
+    }
+}
+
+impl fmt::Display for BasicSchedule {
+    /// Writes the record as a line of CIF
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "BS{}", self.transaction_type.code())?;
+        write_field(f, &self.train_uid, 6)?;
+        write_date(f, self.from)?;
+        write_end_date(f, self.transaction_type, self.from, self.to)?;
+        write_days(f, &self.days)?;
+        write_optional(f, self.bank_holiday_running.as_ref().map(|b| b.code()), 1)?;
+        write_optional(f, self.train_status.as_ref().map(|s| s.code()), 1)?;
+        write_optional(f, self.train_category.as_ref(), 2)?;
+        write_optional(f, self.train_identity.as_ref(), 4)?;
+        write_optional(f, self.headcode.as_ref(), 4)?;
+        write_optional(f, self.course_indicator, 1)?;
+        write_optional(f, self.train_service_code.as_ref(), 8)?;
+        write_optional(f, self.portion_id, 1)?;
+        write_optional(f, self.power_type.as_ref(), 3)?;
+        write_timing_load(
+            f,
+            &self.source,
+            51..55,
+            self.power_type.as_ref(),
+            self.timing_load.as_ref(),
+        )?;
+        write_optional(f, self.speed.map(|speed| format!("{speed:03}")), 3)?;
+        write_field(
+            f,
+            codes(&self.operating_chars, OperatingCharacteristic::code),
+            6,
+        )?;
+        write_optional(f, self.seating_class.as_ref().map(SeatingClass::code), 1)?;
+        write_optional(f, self.sleepers.as_ref().map(Sleepers::code), 1)?;
+        write_optional(f, self.reservations.as_ref().map(Reservations::code), 1)?;
+        write_optional(f, self.connection_indicator, 1)?;
+        write_field(f, codes(&self.catering, Catering::code), 4)?;
+        write_optional(f, self.service_branding.as_ref(), 4)?;
+        write!(f, " {}", self.stp_indicator.code())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for BasicSchedule {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let string = serde::Deserialize::deserialize(deserializer)?;
+
+        BasicSchedule::from_str(string).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Basic Schedule Extra Details Record
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BasicScheduleExtra {
+    /// Traction class, which is no longer used
+    pub traction_class: Option<String>,
+    /// International Union of Railways code, used for international services
+    pub uic_code: Option<String>,
+    pub atoc_code: Option<Atoc>,
+    /// Whether the service is subject to performance monitoring
+    pub applicable_timetable: bool,
+    /// Used by retail systems to identify the service, such as `VT123400`
+    pub retail_service_id: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) source: SourceLine,
+}
+
+impl FromStr for BasicScheduleExtra {
+    type Err = RecordParsingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.is_ascii() {
+            return Err(RecordParsingError::NonAscii);
+        }
+
+        let stripped = match s.len() {
+            78 => s,
+            80 => {
+                if &s[0..2] != "BX" {
+                    return Err(RecordParsingError::UnexpectedRecordIdentity("BX"));
+                }
+
+                &s[2..]
+            }
+            _ => return Err(RecordParsingError::InvalidLength),
+        };
+
+        Ok(BasicScheduleExtra {
+            traction_class: parse_text(&stripped[0..4]),
+            uic_code: parse_text(&stripped[4..9]),
+            atoc_code: parse_optional(&stripped[9..11])
+                .map_err(|_| RecordParsingError::invalid_field("ATOC Code", stripped, 9..11))?,
+            applicable_timetable: parse_applicable_timetable(&stripped[11..12]).ok_or_else(
+                || RecordParsingError::invalid_field("Applicable Timetable Code", stripped, 11..12),
             )?,
+            retail_service_id: parse_text(&stripped[12..20]),
+            source: SourceLine::new(stripped),
         })
     }
 }
 
-impl<'de> Deserialize<'de> for BasicSchedule {
+impl fmt::Display for BasicScheduleExtra {
+    /// Writes the record as a line of CIF
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BX")?;
+        write_optional(f, self.traction_class.as_ref(), 4)?;
+        write_optional(f, self.uic_code.as_ref(), 5)?;
+        write_optional(f, self.atoc_code.as_deref(), 2)?;
+        self.source.write_field(
+            f,
+            11..12,
+            &self.applicable_timetable,
+            parse_applicable_timetable,
+            |f| f.write_str(if self.applicable_timetable { "Y" } else { "N" }),
+        )?;
+        write_optional(f, self.retail_service_id.as_ref(), 8)?;
+        write_field(f, "", 58)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for BasicScheduleExtra {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        let string = Deserialize::deserialize(deserializer)?;
-
-        BasicSchedule::from_str(string).map_err(|e| {
-            serde::de::Error::custom(match e {
-                RecordParsingError::InvalidLength => "invalid length",
-                RecordParsingError::InvalidField(_, _) => "invalid field",
-                RecordParsingError::NonAscii => "non ascii",
-                RecordParsingError::UnexpectedRecordIdentity(_) => "unexpected record type",
-            })
+        Self::from_str(serde::Deserialize::deserialize(deserializer)?)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Parses an applicable timetable code, where a blank code is taken to be `N`
+fn parse_applicable_timetable(field: &str) -> Option<bool> {
+    match field {
+        "Y" => Some(true),
+        "N" | " " => Some(false),
+        _ => None,
+    }
+}
+
+/// Changes en Route Record
+///
+/// Describes the attributes of the train which change from the location it
+/// names onwards. It appears directly before the intermediate location it
+/// applies to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ChangeEnRoute {
+    pub location: Tiploc,
+    pub suffix: Option<char>,
+    pub train_category: Option<TrainCategory>,
+    pub train_identity: Option<String>,
+    pub headcode: Option<String>,
+    pub course_indicator: Option<u8>,
+    pub train_service_code: Option<String>,
+    pub portion_id: Option<char>,
+    pub power_type: Option<PowerType>,
+    pub timing_load: Option<TimingLoad>,
+    pub speed: Option<u16>,
+    pub operating_chars: Vec<OperatingCharacteristic>,
+    pub seating_class: Option<SeatingClass>,
+    pub sleepers: Option<Sleepers>,
+    pub reservations: Option<Reservations>,
+    pub connection_indicator: Option<char>,
+    pub catering: Vec<Catering>,
+    pub service_branding: Option<String>,
+    /// Traction class, which is no longer used
+    pub traction_class: Option<String>,
+    pub uic_code: Option<String>,
+    pub retail_service_id: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) source: SourceLine,
+}
+
+impl FromStr for ChangeEnRoute {
+    type Err = RecordParsingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.is_ascii() {
+            return Err(RecordParsingError::NonAscii);
+        }
+
+        let stripped = match s.len() {
+            78 => s,
+            80 => {
+                if &s[0..2] != "CR" {
+                    return Err(RecordParsingError::UnexpectedRecordIdentity("CR"));
+                }
+
+                &s[2..]
+            }
+            _ => return Err(RecordParsingError::InvalidLength),
+        };
+
+        let power_type: Option<PowerType> = parse_optional(&stripped[28..31]).unwrap_or_default();
+
+        Ok(ChangeEnRoute {
+            location: Tiploc::from_str(&stripped[0..7])
+                .map_err(|_| RecordParsingError::invalid_field("Location", stripped, 0..7))?,
+            suffix: parse_suffix(&stripped[7..8]),
+            train_category: parse_optional(&stripped[8..10]).unwrap_or_default(),
+            train_identity: parse_text(&stripped[10..14]),
+            headcode: parse_text(&stripped[14..18]),
+            course_indicator: parse_optional(&stripped[18..19]).map_err(|_| {
+                RecordParsingError::invalid_field("Course Indicator", stripped, 18..19)
+            })?,
+            train_service_code: parse_text(&stripped[19..27]),
+            portion_id: parse_optional(&stripped[27..28])
+                .map_err(|_| RecordParsingError::invalid_field("Portion Id", stripped, 27..28))?,
+            timing_load: TimingLoad::new(power_type.as_ref(), &stripped[31..35]),
+            power_type,
+            speed: parse_optional(stripped[35..38].trim())
+                .map_err(|_| RecordParsingError::invalid_field("Speed", stripped, 35..38))?,
+            operating_chars: parse_operating_chars(&stripped[38..44]),
+            seating_class: parse_optional(&stripped[44..45]).map_err(|_| {
+                RecordParsingError::invalid_field("Seating Class", stripped, 44..45)
+            })?,
+            sleepers: parse_optional(&stripped[45..46])
+                .map_err(|_| RecordParsingError::invalid_field("Sleepers", stripped, 45..46))?,
+            reservations: parse_optional(&stripped[46..47])
+                .map_err(|_| RecordParsingError::invalid_field("Reservations", stripped, 46..47))?,
+            connection_indicator: parse_optional(&stripped[47..48]).map_err(|_| {
+                RecordParsingError::invalid_field("Connection Indicator", stripped, 47..48)
+            })?,
+            catering: parse_catering(&stripped[48..52]).map_err(|_| {
+                RecordParsingError::invalid_field("Catering Code", stripped, 48..52)
+            })?,
+            service_branding: parse_text(&stripped[52..56]),
+            traction_class: parse_text(&stripped[56..60]),
+            uic_code: parse_text(&stripped[60..65]),
+            retail_service_id: parse_text(&stripped[65..73]),
+            source: SourceLine::new(stripped),
         })
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct BasicScheduleExtra;
+impl fmt::Display for ChangeEnRoute {
+    /// Writes the record as a line of CIF
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CR")?;
+        write_field(f, &self.location, 7)?;
+        write_optional(f, self.suffix, 1)?;
+        write_optional(f, self.train_category.as_ref(), 2)?;
+        write_optional(f, self.train_identity.as_ref(), 4)?;
+        write_optional(f, self.headcode.as_ref(), 4)?;
+        write_optional(f, self.course_indicator, 1)?;
+        write_optional(f, self.train_service_code.as_ref(), 8)?;
+        write_optional(f, self.portion_id, 1)?;
+        write_optional(f, self.power_type.as_ref(), 3)?;
+        write_timing_load(
+            f,
+            &self.source,
+            31..35,
+            self.power_type.as_ref(),
+            self.timing_load.as_ref(),
+        )?;
+        write_optional(f, self.speed.map(|speed| format!("{speed:03}")), 3)?;
+        write_field(
+            f,
+            codes(&self.operating_chars, OperatingCharacteristic::code),
+            6,
+        )?;
+        write_optional(f, self.seating_class.as_ref().map(SeatingClass::code), 1)?;
+        write_optional(f, self.sleepers.as_ref().map(Sleepers::code), 1)?;
+        write_optional(f, self.reservations.as_ref().map(Reservations::code), 1)?;
+        write_optional(f, self.connection_indicator, 1)?;
+        write_field(f, codes(&self.catering, Catering::code), 4)?;
+        write_optional(f, self.service_branding.as_ref(), 4)?;
+        write_optional(f, self.traction_class.as_ref(), 4)?;
+        write_optional(f, self.uic_code.as_ref(), 5)?;
+        write_optional(f, self.retail_service_id.as_ref(), 8)?;
+        write_field(f, "", 5)
+    }
+}
 
-impl<'de> Deserialize<'de> for BasicScheduleExtra {
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ChangeEnRoute {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        let _string: &str = Deserialize::deserialize(deserializer)?;
-        Ok(Self)
+        Self::from_str(serde::Deserialize::deserialize(deserializer)?)
+            .map_err(serde::de::Error::custom)
     }
 }
+
+#[test]
+fn deserialize_bs() -> Result<(), Box<dyn std::error::Error>> {
+    let raw = "BSNP132082412152505110000001 PXX1S758044121730001 EMU397 125      B A F        P";
+
+    let schedule = BasicSchedule::from_str(raw)?;
+
+    assert_eq!(schedule.days, vec![Weekday::Sun]);
+    assert_eq!(
+        schedule.train_status,
+        Some(TrainStatus::PassengerAndParcels)
+    );
+    assert_eq!(schedule.train_identity.as_deref(), Some("1S75"));
+    assert_eq!(schedule.train_service_code.as_deref(), Some("21730001"));
+    assert_eq!(
+        schedule.train_category,
+        Some(TrainCategory::ExpressPassenger)
+    );
+    assert_eq!(schedule.power_type, Some(PowerType::ElectricMultipleUnit));
+    assert_eq!(
+        schedule.timing_load,
+        Some(TimingLoad::ElectricMultipleUnitClass(397))
+    );
+    assert_eq!(schedule.speed, Some(125));
+    assert_eq!(schedule.seating_class, Some(SeatingClass::FirstAndStandard));
+    assert_eq!(schedule.reservations, Some(Reservations::Compulsory));
+    assert_eq!(schedule.catering, vec![Catering::FirstClassRestaurant]);
+
+    Ok(())
+}
+
+#[test]
+fn deserialize_bx() -> Result<(), Box<dyn std::error::Error>> {
+    let raw = "BX         VTYVT123400                                                          ";
+
+    let extra = BasicScheduleExtra::from_str(raw)?;
+
+    assert_eq!(extra.atoc_code.as_deref(), Some("VT"));
+    assert!(extra.applicable_timetable);
+    assert_eq!(extra.retail_service_id.as_deref(), Some("VT123400"));
+    assert_eq!(extra.traction_class, None);
+
+    let raw = "BXABCD     VTYVT123400                                                          ";
+
+    let extra = BasicScheduleExtra::from_str(raw)?;
+
+    assert_eq!(extra.traction_class.as_deref(), Some("ABCD"));
+    assert_eq!(extra.to_string(), raw);
+
+    Ok(())
+}
+
+#[test]
+fn running_dates() -> Result<(), Box<dyn std::error::Error>> {
+    let raw = "BSNC123452505182506011111100XPOO2N75    113575825 EMU    090      S            P";
+
+    let schedule = BasicSchedule::from_str(raw)?;
+    let date = |day| NaiveDate::from_ymd_opt(2025, 5, day).unwrap();
+
+    assert!(schedule.runs_on(date(19), &NoHolidays));
+    assert!(!schedule.runs_on(date(24), &NoHolidays));
+    assert!(!schedule.runs_on(date(17), &NoHolidays));
+    assert_eq!(schedule.running_dates(&NoHolidays).count(), 10);
+
+    let spring_bank_holiday = |date: NaiveDate, holiday: &BankHolidayRunning| {
+        *holiday == BankHolidayRunning::NotOnBankHolidayMondays
+            && date == NaiveDate::from_ymd_opt(2025, 5, 26).unwrap()
+    };
+
+    assert!(!schedule.runs_on(date(26), &spring_bank_holiday));
+    assert_eq!(
+        schedule.running_dates(&spring_bank_holiday).next(),
+        Some(date(19))
+    );
+    assert_eq!(schedule.running_dates(&spring_bank_holiday).count(), 9);
+
+    Ok(())
+}
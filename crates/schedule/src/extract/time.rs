@@ -0,0 +1,426 @@
+use std::{
+    fmt,
+    iter::Sum,
+    ops::{Add, Range, Sub},
+    str::FromStr,
+};
+
+use chrono::{NaiveTime, TimeDelta, Timelike};
+use thiserror::Error;
+
+use super::{parse_optional, SourceLine};
+
+const HALF_MINUTES_PER_DAY: i64 = 24 * 60 * 2;
+
+/// A time of day from a schedule, with half minute precision
+///
+/// Working times, used by train planners and signallers, are encoded in CIF
+/// as five characters such as `1612H`, where a trailing `H` adds half a
+/// minute. Public times, as shown to passengers, are four digits such as
+/// `1612`. Both forms are accepted by [FromStr] and [fmt::Display] writes the
+/// shortest form, so `1612H` and `1612` round trip unchanged.
+///
+/// Arithmetic wraps around midnight, as schedules do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ScheduledTime {
+    half_minutes: u16,
+}
+
+#[derive(Debug, Error)]
+pub enum ScheduledTimeParsingError {
+    #[error("Scheduled time must be 4 or 5 characters")]
+    InvalidLength,
+    #[error("Scheduled time must be in the form HHMM, optionally followed by H")]
+    InvalidCharacter,
+    #[error("Scheduled time is not a valid time of day")]
+    OutOfRange,
+}
+
+impl ScheduledTime {
+    /// Creates a time from its hour, minute and whether it is half a minute
+    /// past, returning `None` if it is not a valid time of day
+    pub fn new(hour: u8, minute: u8, half: bool) -> Option<Self> {
+        if hour > 23 || minute > 59 {
+            return None;
+        }
+
+        Some(ScheduledTime {
+            half_minutes: (hour as u16 * 60 + minute as u16) * 2 + half as u16,
+        })
+    }
+
+    /// Creates a time from the number of half minutes since midnight
+    pub fn from_half_minutes(half_minutes: u16) -> Option<Self> {
+        (i64::from(half_minutes) < HALF_MINUTES_PER_DAY).then_some(ScheduledTime { half_minutes })
+    }
+
+    /// Number of half minutes since midnight
+    pub fn half_minutes(&self) -> u16 {
+        self.half_minutes
+    }
+
+    pub fn hour(&self) -> u8 {
+        (self.half_minutes / 120) as u8
+    }
+
+    pub fn minute(&self) -> u8 {
+        (self.half_minutes / 2 % 60) as u8
+    }
+
+    /// Whether the time is half a minute past the minute
+    pub fn is_half(&self) -> bool {
+        self.half_minutes % 2 == 1
+    }
+
+    /// Moves the time by a number of seconds, rounded towards zero to the
+    /// half minute and wrapping around midnight
+    fn wrapping_add_seconds(self, seconds: i64) -> Self {
+        ScheduledTime {
+            half_minutes: (i64::from(self.half_minutes) + seconds / 30)
+                .rem_euclid(HALF_MINUTES_PER_DAY) as u16,
+        }
+    }
+}
+
+impl FromStr for ScheduledTime {
+    type Err = ScheduledTimeParsingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.is_ascii() {
+            return Err(ScheduledTimeParsingError::InvalidCharacter);
+        }
+
+        let half = match s.len() {
+            4 => false,
+            5 => match &s[4..] {
+                "H" => true,
+                " " => false,
+                _ => return Err(ScheduledTimeParsingError::InvalidCharacter),
+            },
+            _ => return Err(ScheduledTimeParsingError::InvalidLength),
+        };
+
+        if !s[0..4].bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ScheduledTimeParsingError::InvalidCharacter);
+        }
+
+        let hour = s[0..2]
+            .parse()
+            .map_err(|_| ScheduledTimeParsingError::InvalidCharacter)?;
+        let minute = s[2..4]
+            .parse()
+            .map_err(|_| ScheduledTimeParsingError::InvalidCharacter)?;
+
+        ScheduledTime::new(hour, minute, half).ok_or(ScheduledTimeParsingError::OutOfRange)
+    }
+}
+
+impl fmt::Display for ScheduledTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}{:02}", self.hour(), self.minute())?;
+
+        if self.is_half() {
+            f.write_str("H")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Serializes as a structure such as `{"hour": 16, "minute": 12, "second": 30}`,
+/// where the second is either `0` or `30`
+#[cfg(feature = "serde")]
+impl serde::Serialize for ScheduledTime {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut time = serializer.serialize_struct("ScheduledTime", 3)?;
+        time.serialize_field("hour", &self.hour())?;
+        time.serialize_field("minute", &self.minute())?;
+        time.serialize_field("second", &if self.is_half() { 30u8 } else { 0 })?;
+        time.end()
+    }
+}
+
+impl From<ScheduledTime> for NaiveTime {
+    fn from(value: ScheduledTime) -> Self {
+        NaiveTime::from_num_seconds_from_midnight_opt(u32::from(value.half_minutes) * 30, 0)
+            .expect("scheduled times are always within a day")
+    }
+}
+
+impl From<NaiveTime> for ScheduledTime {
+    /// Converts a time of day, rounding down to the half minute
+    fn from(value: NaiveTime) -> Self {
+        ScheduledTime {
+            half_minutes: (value.num_seconds_from_midnight() / 30) as u16,
+        }
+    }
+}
+
+impl Add<TimeDelta> for ScheduledTime {
+    type Output = ScheduledTime;
+
+    /// Adds a duration, rounded towards zero to the half minute
+    fn add(self, rhs: TimeDelta) -> Self::Output {
+        self.wrapping_add_seconds(rhs.num_seconds())
+    }
+}
+
+impl Sub<TimeDelta> for ScheduledTime {
+    type Output = ScheduledTime;
+
+    /// Subtracts a duration, rounded towards zero to the half minute
+    fn sub(self, rhs: TimeDelta) -> Self::Output {
+        self.wrapping_add_seconds(-rhs.num_seconds())
+    }
+}
+
+impl Sub for ScheduledTime {
+    type Output = TimeDelta;
+
+    /// Time elapsed from `rhs` until `self`
+    ///
+    /// If `self` is earlier in the day than `rhs` it is taken to be on the
+    /// following day, so the result is never negative.
+    fn sub(self, rhs: ScheduledTime) -> Self::Output {
+        let half_minutes = (i64::from(self.half_minutes) - i64::from(rhs.half_minutes))
+            .rem_euclid(HALF_MINUTES_PER_DAY);
+
+        TimeDelta::seconds(half_minutes * 30)
+    }
+}
+
+/// Time added to a schedule, with half minute precision
+///
+/// Encoded in CIF as up to two characters, where digits give whole minutes
+/// and a trailing `H` adds half a minute, such as `1`, `1H` or `H`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Allowance {
+    half_minutes: u16,
+}
+
+#[derive(Debug, Error)]
+pub enum AllowanceParsingError {
+    #[error("Allowance must be minutes, optionally followed by H")]
+    InvalidCharacter,
+}
+
+impl Allowance {
+    pub fn from_half_minutes(half_minutes: u16) -> Self {
+        Allowance { half_minutes }
+    }
+
+    pub fn half_minutes(&self) -> u16 {
+        self.half_minutes
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.half_minutes == 0
+    }
+}
+
+impl FromStr for Allowance {
+    type Err = AllowanceParsingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        let (minutes, half) = match s.strip_suffix('H') {
+            Some(minutes) => (minutes, 1),
+            None => (s, 0),
+        };
+
+        if !minutes.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(AllowanceParsingError::InvalidCharacter);
+        }
+
+        let minutes: u16 = match minutes {
+            "" if half == 1 => 0,
+            minutes => minutes
+                .parse()
+                .map_err(|_| AllowanceParsingError::InvalidCharacter)?,
+        };
+
+        Ok(Allowance {
+            half_minutes: minutes * 2 + half,
+        })
+    }
+}
+
+impl fmt::Display for Allowance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let minutes = self.half_minutes / 2;
+
+        if minutes > 0 || self.half_minutes == 0 {
+            write!(f, "{minutes}")?;
+        }
+
+        if self.half_minutes % 2 == 1 {
+            f.write_str("H")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Serializes as a structure such as `{"minutes": 1, "seconds": 30}`, where
+/// the seconds are either `0` or `30`
+#[cfg(feature = "serde")]
+impl serde::Serialize for Allowance {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut allowance = serializer.serialize_struct("Allowance", 2)?;
+        allowance.serialize_field("minutes", &(self.half_minutes / 2))?;
+        allowance.serialize_field("seconds", &(self.half_minutes % 2 * 30))?;
+        allowance.end()
+    }
+}
+
+impl Add for Allowance {
+    type Output = Allowance;
+
+    fn add(self, rhs: Allowance) -> Self::Output {
+        Allowance {
+            half_minutes: self.half_minutes + rhs.half_minutes,
+        }
+    }
+}
+
+impl Sum for Allowance {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Allowance::default(), Add::add)
+    }
+}
+
+impl From<Allowance> for TimeDelta {
+    fn from(value: Allowance) -> Self {
+        TimeDelta::seconds(i64::from(value.half_minutes) * 30)
+    }
+}
+
+impl Add<Allowance> for ScheduledTime {
+    type Output = ScheduledTime;
+
+    fn add(self, rhs: Allowance) -> Self::Output {
+        self + TimeDelta::from(rhs)
+    }
+}
+
+/// Parses a public time, where `0000` means there is no public time
+pub(crate) fn parse_public_time(
+    field: &str,
+) -> Result<Option<ScheduledTime>, ScheduledTimeParsingError> {
+    match field.trim() {
+        "" | "0000" => Ok(None),
+        time => time.parse().map(Some),
+    }
+}
+
+/// Writes a two character allowance field as it was laid out when read, or
+/// leaves it blank when there is no allowance
+///
+/// Otherwise minutes are written from the first character and a half minute
+/// always takes the second, so half a minute on its own is written as ` H`.
+pub(crate) fn write_allowance(
+    f: &mut fmt::Formatter<'_>,
+    source: &SourceLine,
+    range: Range<usize>,
+    allowance: Option<Allowance>,
+) -> fmt::Result {
+    source.write_field(
+        f,
+        range,
+        &allowance,
+        |text| parse_optional(text).ok(),
+        |f| match allowance.map(|allowance| allowance.to_string()).as_deref() {
+            Some("H") => f.write_str(" H"),
+            Some(allowance) => write!(f, "{allowance:<2}"),
+            None => f.write_str("  "),
+        },
+    )
+}
+
+/// Writes a public time, keeping a blank field blank, or as `0000` when there
+/// is no public time
+pub(crate) fn write_public_time(
+    f: &mut fmt::Formatter<'_>,
+    source: &SourceLine,
+    range: Range<usize>,
+    time: Option<ScheduledTime>,
+) -> fmt::Result {
+    source.write_field(
+        f,
+        range,
+        &time,
+        |text| parse_public_time(text).ok(),
+        |f| match time {
+            Some(time) => write!(f, "{time}"),
+            None => f.write_str("0000"),
+        },
+    )
+}
+
+#[test]
+fn scheduled_time_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    for raw in ["1610", "1612H", "0000", "2359H"] {
+        assert_eq!(raw.parse::<ScheduledTime>()?.to_string(), raw);
+    }
+
+    let working: ScheduledTime = "1619H".parse()?;
+    let public: ScheduledTime = "1619".parse()?;
+
+    assert!(public < working);
+    assert_eq!(working - public, TimeDelta::seconds(30));
+    assert_eq!(
+        NaiveTime::from(working),
+        NaiveTime::from_hms_opt(16, 19, 30).unwrap()
+    );
+
+    let late: ScheduledTime = "2359H".parse()?;
+    assert_eq!((late + TimeDelta::minutes(2)).to_string(), "0001H");
+    assert_eq!(
+        "0001".parse::<ScheduledTime>()? - late,
+        TimeDelta::seconds(90)
+    );
+
+    assert!("2460".parse::<ScheduledTime>().is_err());
+    assert!("16A0".parse::<ScheduledTime>().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn allowance_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    for (raw, half_minutes) in [("H", 1), ("1", 2), ("1H", 3), ("10", 20)] {
+        let allowance: Allowance = raw.parse()?;
+
+        assert_eq!(allowance.half_minutes(), half_minutes);
+        assert_eq!(allowance.to_string(), raw);
+    }
+
+    assert_eq!(" H".parse::<Allowance>()?, Allowance::from_half_minutes(1));
+    assert!("".parse::<Allowance>().is_err());
+    assert!("X".parse::<Allowance>().is_err());
+
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serialize_structured() -> Result<(), Box<dyn std::error::Error>> {
+    let time: ScheduledTime = "0712H".parse()?;
+    let allowance: Allowance = "1H".parse()?;
+
+    assert_eq!(
+        serde_json::to_string(&time)?,
+        r#"{"hour":7,"minute":12,"second":30}"#
+    );
+    assert_eq!(
+        serde_json::to_string(&allowance)?,
+        r#"{"minutes":1,"seconds":30}"#
+    );
+
+    Ok(())
+}
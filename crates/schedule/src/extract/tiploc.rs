@@ -1,30 +1,35 @@
-use std::str::FromStr;
-
-use serde::{
-    de::{
-        self,
-        value::{BorrowedStrDeserializer, StrDeserializer},
-        IntoDeserializer, Visitor,
-    },
-    Deserialize,
-};
+use std::{fmt, str::FromStr};
 
 use crate::error::RecordParsingError;
 
 use super::{
     identifier::Crs,
     location::{Nalco, PoMcpCode, Stanox, Tiploc},
+    parse_optional, write_field, write_optional,
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 /// Timing Point Location (TIPLOC) Insert Record
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct TiplocInsert {
     pub code: Tiploc,
+    /// Capitals identification, used to sort locations in printed
+    /// timetables, such as `00`
+    #[cfg_attr(feature = "serde", serde(serialize_with = "super::serialize_text"))]
+    pub capitals: String,
     pub nalco: Nalco,
+    /// Check character of the National Location Code
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "super::serialize_character")
+    )]
+    pub nlc_check_character: char,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "super::serialize_text"))]
     pub tps_description: String,
     pub stanox: Stanox,
     pub po_mcp_code: PoMcpCode,
     pub crs: Crs,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "super::serialize_text"))]
     pub description: String,
 }
 
@@ -49,52 +54,83 @@ impl FromStr for TiplocInsert {
         };
 
         Ok(TiplocInsert {
-            code: stripped[0..7].parse().map_err(|_| {
-                RecordParsingError::InvalidField("TIPLOC", stripped[0..7].to_string())
-            })?,
+            code: stripped[0..7]
+                .parse()
+                .map_err(|_| RecordParsingError::invalid_field("TIPLOC", stripped, 0..7))?,
+            capitals: stripped[7..9].to_string(),
             nalco: stripped[9..15].parse().map_err(|_| {
-                RecordParsingError::InvalidField(
-                    "National Location Code",
-                    stripped[9..15].to_string(),
-                )
+                RecordParsingError::invalid_field("National Location Code", stripped, 9..15)
             })?,
+            nlc_check_character: stripped.as_bytes()[15] as char,
             tps_description: stripped[16..42].to_string(),
-            stanox: Stanox::from_str(&stripped[42..47]).map_err(|_| {
-                RecordParsingError::InvalidField("Stanox", stripped[42..47].to_string())
-            })?,
-            po_mcp_code: PoMcpCode::from_str(&stripped[47..51]).map_err(|_| {
-                RecordParsingError::InvalidField("PO MCP Code", stripped[46..50].to_string())
-            })?,
-            crs: Crs::from_str(&stripped[51..54]).map_err(|_| {
-                RecordParsingError::InvalidField("CRS Code", stripped[50..53].to_string())
-            })?,
+            stanox: Stanox::from_str(&stripped[42..47])
+                .map_err(|_| RecordParsingError::invalid_field("Stanox", stripped, 42..47))?,
+            po_mcp_code: PoMcpCode::from_str(&stripped[47..51])
+                .map_err(|_| RecordParsingError::invalid_field("PO MCP Code", stripped, 47..51))?,
+            crs: Crs::from_str(&stripped[51..54])
+                .map_err(|_| RecordParsingError::invalid_field("CRS Code", stripped, 51..54))?,
             description: stripped[54..70].to_string(),
         })
     }
 }
 
-impl<'de> Deserialize<'de> for TiplocInsert {
+impl TiplocInsert {
+    /// Writes the details shared by inserts and amendments
+    fn write_details(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_field(f, &self.code, 7)?;
+        write_field(f, &self.capitals, 2)?;
+        write_field(f, &*self.nalco, 6)?;
+        write!(f, "{}", self.nlc_check_character)?;
+        write_field(f, &self.tps_description, 26)?;
+        write_field(f, &*self.stanox, 5)?;
+        write_field(f, &*self.po_mcp_code, 4)?;
+        write_field(f, &*self.crs, 3)?;
+        write_field(f, &self.description, 16)
+    }
+}
+
+impl fmt::Display for TiplocInsert {
+    /// Writes the record as a line of CIF
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TI")?;
+        self.write_details(f)?;
+        write_field(f, "", 8)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for TiplocInsert {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        Self::from_str(Deserialize::deserialize(deserializer)?).map_err(|e| de::Error::custom(e))
+        Self::from_str(serde::Deserialize::deserialize(deserializer)?)
+            .map_err(serde::de::Error::custom)
     }
 }
 
 #[test]
 fn deserialize_ti() -> Result<(), Box<dyn std::error::Error>> {
     let raw = "TIAACHEN 00081601LAACHEN                    00005   0                           ";
-    let deserializer = BorrowedStrDeserializer::<de::value::Error>::new(raw);
 
-    let _ = TiplocInsert::deserialize(deserializer)?;
+    let insert = TiplocInsert::from_str(raw)?;
+
+    assert_eq!(&*insert.code, "AACHEN");
+    assert_eq!(&*insert.nalco, "081601");
 
     Ok(())
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 /// TIPLOC Amend Record
-pub struct TiplocAmend;
+///
+/// Carries the full set of details for the location. When the TIPLOC itself
+/// is being renamed, `new_code` holds the code it is known by from now on.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TiplocAmend {
+    pub details: TiplocInsert,
+    pub new_code: Option<Tiploc>,
+}
 
 impl FromStr for TiplocAmend {
     type Err = RecordParsingError;
@@ -115,22 +151,54 @@ impl FromStr for TiplocAmend {
             _ => return Err(RecordParsingError::InvalidLength),
         };
 
-        Ok(Self)
+        Ok(TiplocAmend {
+            details: TiplocInsert::from_str(stripped)?,
+            new_code: parse_optional(&stripped[70..77])
+                .map_err(|_| RecordParsingError::invalid_field("New TIPLOC", stripped, 70..77))?,
+        })
+    }
+}
+
+impl fmt::Display for TiplocAmend {
+    /// Writes the record as a line of CIF
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TA")?;
+        self.details.write_details(f)?;
+        write_optional(f, self.new_code.as_ref(), 7)?;
+        write_field(f, "", 1)
     }
 }
 
-impl<'de> Deserialize<'de> for TiplocAmend {
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for TiplocAmend {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
-        D: de::Deserializer<'de>,
+        D: serde::Deserializer<'de>,
     {
-        Self::from_str(Deserialize::deserialize(deserializer)?).map_err(|e| de::Error::custom(e))
+        Self::from_str(serde::Deserialize::deserialize(deserializer)?)
+            .map_err(serde::de::Error::custom)
     }
 }
 
-#[derive(Debug)]
+#[test]
+fn deserialize_ta() -> Result<(), Box<dyn std::error::Error>> {
+    let raw = "TAAACHEN 00081601LAACHEN HBF                00005   0   AACHEN HBF      AACHENH ";
+
+    let amend = TiplocAmend::from_str(raw)?;
+
+    assert_eq!(&*amend.details.code, "AACHEN");
+    assert_eq!(amend.details.tps_description, "AACHEN HBF                ");
+    assert_eq!(amend.new_code.as_deref(), Some("AACHENH"));
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 /// TIPLOC Delete Record
-pub struct TiplocDelete;
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TiplocDelete {
+    pub code: Tiploc,
+}
 
 impl FromStr for TiplocDelete {
     type Err = RecordParsingError;
@@ -151,15 +219,30 @@ impl FromStr for TiplocDelete {
             _ => return Err(RecordParsingError::InvalidLength),
         };
 
-        Ok(Self)
+        Ok(TiplocDelete {
+            code: stripped[0..7]
+                .parse()
+                .map_err(|_| RecordParsingError::invalid_field("TIPLOC", stripped, 0..7))?,
+        })
+    }
+}
+
+impl fmt::Display for TiplocDelete {
+    /// Writes the record as a line of CIF
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TD")?;
+        write_field(f, &self.code, 7)?;
+        write_field(f, "", 71)
     }
 }
 
-impl<'de> Deserialize<'de> for TiplocDelete {
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for TiplocDelete {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
-        D: de::Deserializer<'de>,
+        D: serde::Deserializer<'de>,
     {
-        Self::from_str(Deserialize::deserialize(deserializer)?).map_err(|e| de::Error::custom(e))
+        Self::from_str(serde::Deserialize::deserialize(deserializer)?)
+            .map_err(serde::de::Error::custom)
     }
 }
@@ -0,0 +1,50 @@
+use std::{fmt, str::FromStr};
+
+use crate::error::RecordParsingError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Trailer Record
+///
+/// Marks the end of an extract. A file without one has been truncated.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Trailer;
+
+impl FromStr for Trailer {
+    type Err = RecordParsingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.is_ascii() {
+            return Err(RecordParsingError::NonAscii);
+        }
+
+        match s.len() {
+            78 => Ok(Trailer),
+            80 => {
+                if &s[0..2] != "ZZ" {
+                    return Err(RecordParsingError::UnexpectedRecordIdentity("ZZ"));
+                }
+
+                Ok(Trailer)
+            }
+            _ => Err(RecordParsingError::InvalidLength),
+        }
+    }
+}
+
+impl fmt::Display for Trailer {
+    /// Writes the record as a line of CIF
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ZZ{:78}", "")
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Trailer {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Self::from_str(serde::Deserialize::deserialize(deserializer)?)
+            .map_err(serde::de::Error::custom)
+    }
+}
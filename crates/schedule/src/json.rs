@@ -0,0 +1,44 @@
+//! Exporting extracts as JSON Lines, with the `json` feature
+//!
+//! [write_json_lines] converts an extract into newline delimited JSON, with an
+//! object on each line for every train schedule, TIPLOC and association. Each
+//! object has a `type` naming what it is, alongside the fields described in
+//! the [crate] documentation, such as
+//! `{"type":"tiploc_insert","code":"AACHEN",...}`.
+
+use std::io::{self, BufRead, Write};
+
+use crate::{
+    error::{Error, Result},
+    reader::{Entry, RecordReader},
+};
+
+/// Writes an extract as JSON Lines, in the order the extract lists them
+///
+/// Each [Entry] other than the header and trailer is written on its own line.
+/// The extract is read a line at a time and each object is written as soon
+/// as it is complete, so only one train schedule is held in memory. Lines are
+/// written one at a time, so `writer` is best buffered.
+///
+/// Everything before the end of the extract has been written by the time a
+/// missing trailer is reported as [Error::Incomplete].
+pub fn write_json_lines<R: BufRead, W: Write>(reader: R, mut writer: W) -> Result<()> {
+    for entry in RecordReader::new(reader).entries() {
+        match entry? {
+            Entry::Header(_) => {}
+            Entry::Trailer(_) => return Ok(writer.flush()?),
+            entry => write_entry(&mut writer, &entry)?,
+        }
+    }
+
+    writer.flush()?;
+
+    Err(Error::Incomplete)
+}
+
+fn write_entry<W: Write>(writer: &mut W, entry: &Entry) -> Result<()> {
+    serde_json::to_writer(&mut *writer, entry).map_err(io::Error::from)?;
+    writer.write_all(b"\n")?;
+
+    Ok(())
+}
@@ -1,240 +1,67 @@
 //! # Common Interface File
-
-use error::{Error, Result};
-use serde;
-use serde::de::{self, DeserializeSeed, SeqAccess};
+//!
+//! ## Serializing
+//!
+//! With the `serde` feature, parsed records and [timetable::Timetable]
+//! implement `Serialize`, so they can be written as JSON. The shape is stable:
+//!
+//! - Structs are objects keyed by their field names, such as
+//!   `{"header": {...}, "train_schedules": [...]}` for a timetable, and
+//!   optional fields are `null` when absent.
+//! - Dates are ISO 8601 strings, such as `"2025-02-28"`, and the date and time
+//!   of an extract is `"2025-02-28T21:54:00"`.
+//! - Days of the week are lists of their abbreviations, such as
+//!   `["Mon", "Tue"]`.
+//! - Times are objects, such as `{"hour": 16, "minute": 12, "second": 30}`,
+//!   and allowances are `{"minutes": 1, "seconds": 30}`. Seconds are always
+//!   `0` or `30`.
+//! - Codes, such as the STP indicator, power type and activities, are the
+//!   strings used in CIF, such as `"P"`, `"EMU"` and `"TB"`. Codes unknown to
+//!   this crate are kept as they were found.
+//! - Text, including identifiers such as TIPLOCs and train UIDs, is trimmed of
+//!   the padding used in CIF, such as `"AACHEN HBF"`. Blank text is `null`,
+//!   as are a Stanox of `00000` and a PO MCP code of `0000`, which CIF uses
+//!   for no code.
+//! - A [extract::Record] is an object keyed by its record identity, such as
+//!   `{"BS": {...}}`.
+//!
+//! With the `json` feature, `json::write_json_lines` streams a whole extract
+//! as JSON Lines, which the `cif-jsonl` command does from the command line.
 
 pub mod error;
 pub mod extract;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod reader;
+#[cfg(feature = "tokio")]
+pub mod stream;
 pub mod timetable;
+pub mod writer;
 
-impl<'de> Deserializer<'de> {
-    pub fn from_str(input: &'de str) -> Self {
-        Deserializer { input }
-    }
-
-    fn peek_char(&mut self) -> Result<char> {
-        self.input.chars().next().ok_or(Error::Eof)
-    }
-
-    // Consume the first character in the input.
-    fn next_char(&mut self) -> Result<char> {
-        let ch = self.peek_char()?;
-        self.input = &self.input[ch.len_utf8()..];
-        Ok(ch)
-    }
-
-    fn parse_string(&mut self) -> Result<&'de str> {
-        match self.input.find('\n') {
-            Some(len) => {
-                let s = &self.input[..len];
-                self.input = &self.input[len..];
-                Ok(s)
-            }
-            None => {
-                let len = self.input.len();
-                let s = &self.input[..len];
-                self.input = &self.input[len..];
-                Ok(s)
-            }
-        }
-    }
-}
-
-pub struct Deserializer<'de> {
-    input: &'de str,
-}
-
-impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
-    type Error = error::Error;
-
-    serde::forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char
-        byte_buf option unit unit_struct newtype_struct tuple tuple_struct
-        map struct ignored_any bytes
-    }
-
-    fn deserialize_str<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
-    where
-        V: de::Visitor<'de>,
-    {
-        visitor.visit_borrowed_str(self.parse_string()?)
-    }
-
-    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
-    where
-        V: de::Visitor<'de>,
-    {
-        self.deserialize_str(visitor)
-    }
-
-    fn deserialize_any<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
-    where
-        V: de::Visitor<'de>,
-    {
-        if self.input.lines().count() > 1 {
-            return self.deserialize_seq(visitor);
-        }
-
-        Err(de::Error::custom(
-            "unsupported type provided to deserializer, only str is supported",
-        ))
-    }
-
-    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
-    where
-        V: de::Visitor<'de>,
-    {
-        visitor.visit_seq(LineSeparated::new(self))
-    }
-
-    fn deserialize_enum<V>(
-        self,
-        _name: &'static str,
-        _variants: &'static [&'static str],
-        visitor: V,
-    ) -> std::result::Result<V::Value, Self::Error>
-    where
-        V: de::Visitor<'de>,
-    {
-        visitor.visit_enum(Enum::new(self))
-    }
-
-    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
-    where
-        V: de::Visitor<'de>,
-    {
-        let id = &self.input[..2];
-        self.input = &self.input[2..];
-
-        visitor.visit_str(id)
-    }
-}
-
-pub fn from_str<'a, T: de::Deserialize<'a>>(input: &'a str) -> error::Result<T> {
-    let mut deserializer = Deserializer::from_str(input);
-    let t = T::deserialize(&mut deserializer)?;
-
-    Ok(t)
-}
-
-// In order to handle commas correctly when deserializing a JSON array or map,
-// we need to track whether we are on the first element or past the first
-// element.
-struct LineSeparated<'a, 'de: 'a> {
-    de: &'a mut Deserializer<'de>,
-    first: bool,
-}
-
-impl<'a, 'de> LineSeparated<'a, 'de> {
-    fn new(de: &'a mut Deserializer<'de>) -> Self {
-        LineSeparated { de, first: true }
-    }
-}
-
-// `SeqAccess` is provided to the `Visitor` to give it the ability to iterate
-// through elements of the sequence.
-impl<'de, 'a> SeqAccess<'de> for LineSeparated<'a, 'de> {
-    type Error = error::Error;
-
-    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
-    where
-        T: DeserializeSeed<'de>,
-    {
-        let input = self.de.input;
+#[cfg(feature = "serde")]
+mod de;
 
-        let peek_char = self.de.peek_char();
+#[cfg(feature = "serde")]
+pub use de::{from_reader, from_str, from_str_lenient, Deserializer};
+pub use reader::Mode;
 
-        // Check if there are no more elements.
-        if peek_char.is_err_and(|e| matches!(e, Error::Eof)) {
-            return Ok(None);
-        }
+/// Writes a value as CIF to a string
+///
+/// A parsed extract is written back exactly as it was read.
+pub fn to_string<T: writer::ToCif + ?Sized>(value: &T) -> String {
+    let mut serializer = writer::Serializer::new(Vec::new());
 
-        // Line ending is required before every element except the first.
-        if !self.first && self.de.next_char()? != '\n' {
-            return Err(Error::Syntax("Expected newline".to_string()));
-        }
+    serializer
+        .serialize(value)
+        .expect("writing to a Vec does not fail");
 
-        self.first = false;
-
-        // Needs revisiting
-
-        Ok(match seed.deserialize(&mut *self.de) {
-            Ok(value) => Some(value),
-            Err(_) => {
-                self.de.input = input;
-                None
-            }
-        })
-    }
+    String::from_utf8(serializer.into_inner()).expect("records are written as UTF-8")
 }
 
-struct Enum<'a, 'de: 'a> {
-    de: &'a mut Deserializer<'de>,
-}
-
-impl<'a, 'de> Enum<'a, 'de> {
-    fn new(de: &'a mut Deserializer<'de>) -> Self {
-        Enum { de }
-    }
-}
-
-// `EnumAccess` is provided to the `Visitor` to give it the ability to determine
-// which variant of the enum is supposed to be deserialized.
-//
-// Note that all enum deserialization methods in Serde refer exclusively to the
-// "externally tagged" enum representation.
-impl<'de, 'a> de::EnumAccess<'de> for Enum<'a, 'de> {
-    type Error = Error;
-    type Variant = Self;
-
-    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
-    where
-        V: DeserializeSeed<'de>,
-    {
-        let val = seed.deserialize(&mut *self.de)?;
-
-        Ok((val, self))
-    }
-}
-
-// `VariantAccess` is provided to the `Visitor` to give it the ability to see
-// the content of the single variant that it decided to deserialize.
-impl<'de, 'a> de::VariantAccess<'de> for Enum<'a, 'de> {
-    type Error = Error;
-
-    // If the `Visitor` expected this variant to be a unit variant, the input
-    // should have been the plain string case handled in `deserialize_enum`.
-    fn unit_variant(self) -> Result<()> {
-        let _ = self.de.parse_string()?;
-
-        Ok(())
-    }
-
-    // Newtype variants are represented in JSON as `{ NAME: VALUE }` so
-    // deserialize the value here.
-    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
-    where
-        T: DeserializeSeed<'de>,
-    {
-        seed.deserialize(self.de)
-    }
-
-    // Tuple variants are represented in JSON as `{ NAME: [DATA...] }` so
-    // deserialize the sequence of data here.
-    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
-    where
-        V: de::Visitor<'de>,
-    {
-        de::Deserializer::deserialize_seq(self.de, visitor)
-    }
-
-    // Struct variants are represented in JSON as `{ NAME: { K: V, ... } }` so
-    // deserialize the inner map here.
-    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
-    where
-        V: de::Visitor<'de>,
-    {
-        de::Deserializer::deserialize_map(self.de, visitor)
-    }
+/// Writes a value as CIF to a writer
+pub fn to_writer<W: std::io::Write, T: writer::ToCif + ?Sized>(
+    writer: W,
+    value: &T,
+) -> error::Result<()> {
+    writer::Serializer::new(writer).serialize(value)
 }
@@ -0,0 +1,512 @@
+//! Reading extracts a line at a time
+//!
+//! [RecordReader] reads records from any [BufRead] without holding the rest
+//! of the extract in memory, and [ScheduleReader] groups them into
+//! [TrainSchedule]s. In [Mode::Lenient], malformed lines are skipped and
+//! reported instead of ending the read. [Entries] goes through the whole
+//! extract in order, with the records of each train schedule grouped
+//! together. With the `gzip` feature, [Decompress] reads extracts which may
+//! or may not be gzip compressed.
+
+#[cfg(feature = "gzip")]
+use std::io::{self, BufReader, Read};
+use std::{collections::VecDeque, io::BufRead, str::FromStr};
+
+#[cfg(feature = "gzip")]
+use flate2::bufread::MultiGzDecoder;
+
+use crate::{
+    error::{Diagnostic, Error, Result},
+    extract::{Association, Header, Record, TiplocAmend, TiplocDelete, TiplocInsert, Trailer},
+    timetable::{ScheduleBuilder, ScheduleRecord, TrainSchedule},
+};
+
+/// How malformed lines are handled while reading
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Fail on the first malformed line
+    #[default]
+    Strict,
+    /// Skip malformed lines, recording a [Diagnostic] for each
+    ///
+    /// A train schedule whose basic schedule is malformed is skipped as a
+    /// whole, with a diagnostic for the basic schedule alone. So is one which
+    /// can't be built without a malformed record, such as its origin location,
+    /// with a diagnostic for each malformed record.
+    Lenient,
+}
+
+/// Identities of the records following a basic schedule
+pub(crate) const SCHEDULE_IDENTITIES: [&str; 7] = ["BX", "TN", "LO", "LI", "CR", "LT", "LN"];
+
+/// Malformed records skipped in [Mode::Lenient]
+#[derive(Debug, Default)]
+pub(crate) struct Skipped {
+    pub(crate) diagnostics: Vec<Diagnostic>,
+    /// Whether the schedule being read started with a malformed record, so
+    /// the rest of it is skipped too
+    schedule: bool,
+    /// Records of the schedule being read, held back until it ends in case it
+    /// can't be built
+    held: Vec<Result<Record>>,
+    /// Records kept, ready to be returned
+    kept: VecDeque<Result<Record>>,
+}
+
+impl Skipped {
+    /// Records a malformed record as skipped, returning anything else to be
+    /// kept
+    pub(crate) fn skip(&mut self, record: Result<Record>) -> Option<Result<Record>> {
+        match record {
+            Ok(
+                Record::BX(_)
+                | Record::TN(_)
+                | Record::LO(_)
+                | Record::LI(_)
+                | Record::CR(_)
+                | Record::LT(_)
+                | Record::LN(_),
+            ) if self.schedule => None,
+            Ok(record) => {
+                self.schedule = false;
+                Some(Ok(record))
+            }
+            Err(error) => match error.into_diagnostic() {
+                Ok(diagnostic) => {
+                    self.schedule = diagnostic.identity == "BS"
+                        || (self.schedule && SCHEDULE_IDENTITIES.contains(&&*diagnostic.identity));
+                    self.diagnostics.push(diagnostic);
+                    None
+                }
+                Err(error) => Some(Err(error)),
+            },
+        }
+    }
+
+    /// Skips the records of a schedule as a whole if it can't be built once
+    /// its malformed records are skipped, giving them back otherwise
+    ///
+    /// `records` run from the basic schedule to the last record of the
+    /// schedule. A schedule which is incomplete without any malformed records
+    /// is given back, to fail as it would in [Mode::Strict].
+    pub(crate) fn skip_schedule(
+        &mut self,
+        records: Vec<Result<Record>>,
+    ) -> Option<Vec<Result<Record>>> {
+        if records.iter().all(Result::is_ok) {
+            return Some(records);
+        }
+
+        let mut kept = records.iter().filter_map(|record| record.as_ref().ok());
+        let Some(Record::BS(bs)) = kept.next() else {
+            return Some(records);
+        };
+
+        let mut builder = ScheduleBuilder::new(bs.clone());
+        for record in kept {
+            if let Ok(record) = ScheduleRecord::try_from(record.clone()) {
+                builder.push(record);
+            }
+        }
+
+        if builder.build().is_ok() {
+            return Some(records);
+        }
+
+        for record in records.into_iter().filter(Result::is_err) {
+            self.skip(record);
+        }
+
+        None
+    }
+
+    /// Returns the next record to keep, reading more with `read` as needed
+    ///
+    /// The records of each schedule are held back until it ends, so that one
+    /// which can't be built is skipped as a whole.
+    pub(crate) fn next(
+        &mut self,
+        mut read: impl FnMut() -> Option<Result<Record>>,
+    ) -> Option<Result<Record>> {
+        loop {
+            if let Some(record) = self.kept.pop_front() {
+                return Some(record);
+            }
+
+            match read() {
+                Some(record) => self.push(record),
+                None if self.held.is_empty() => return None,
+                None => self.release(),
+            }
+        }
+    }
+
+    fn push(&mut self, record: Result<Record>) {
+        let identity = match &record {
+            Ok(record) => record.identity(),
+            Err(Error::Record { identity, .. }) => identity,
+            Err(_) => "",
+        };
+
+        if !self.held.is_empty() && SCHEDULE_IDENTITIES.contains(&identity) {
+            self.held.push(record);
+            return;
+        }
+
+        self.release();
+
+        if let Ok(Record::BS(_)) = record {
+            self.held.push(record);
+        } else if let Some(record) = self.skip(record) {
+            self.kept.push_back(record);
+        }
+    }
+
+    /// Releases the records of the schedule held back
+    fn release(&mut self) {
+        let held = std::mem::take(&mut self.held);
+
+        for record in self.skip_schedule(held).into_iter().flatten() {
+            if let Some(record) = self.skip(record) {
+                self.kept.push_back(record);
+            }
+        }
+    }
+}
+
+/// Parses a line of an extract, returning `None` for blank lines
+///
+/// `number` is the line's position in the extract, starting from 1, which is
+/// reported along with any error.
+pub(crate) fn parse_line(number: usize, line: &str) -> Option<Result<Record>> {
+    let line = line.trim_end_matches(['\n', '\r']);
+
+    (!line.is_empty())
+        .then(|| Record::from_str(line).map_err(|source| Error::record(number, line, source)))
+}
+
+/// Iterator over the records of an extract, read one line at a time
+///
+/// Only the current line is held in memory, along with the records of the
+/// current train schedule in [Mode::Lenient].
+pub struct RecordReader<R> {
+    reader: R,
+    line: String,
+    line_number: usize,
+    mode: Mode,
+    skipped: Skipped,
+}
+
+impl<R: BufRead> RecordReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_mode(reader, Mode::Strict)
+    }
+
+    pub fn with_mode(reader: R, mode: Mode) -> Self {
+        RecordReader {
+            reader,
+            line: String::with_capacity(82),
+            line_number: 0,
+            mode,
+            skipped: Skipped::default(),
+        }
+    }
+
+    /// Lines skipped so far in [Mode::Lenient]
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.skipped.diagnostics
+    }
+
+    pub fn into_diagnostics(self) -> Vec<Diagnostic> {
+        self.skipped.diagnostics
+    }
+
+    /// Groups the records into train schedules, skipping every other record
+    pub fn schedules(self) -> ScheduleReader<R> {
+        ScheduleReader::new(self)
+    }
+
+    /// Groups the records of each train schedule, keeping every other record
+    pub fn entries(self) -> Entries<Self> {
+        Entries::new(self)
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl<R: BufRead> RecordReader<Decompress<R>> {
+    /// Reads an extract which may be gzip compressed, decompressing it as it
+    /// is read if so
+    pub fn decompress(reader: R) -> io::Result<Self> {
+        Ok(RecordReader::new(Decompress::new(reader)?))
+    }
+}
+
+impl<R: BufRead> RecordReader<R> {
+    fn read_record(&mut self) -> Option<Result<Record>> {
+        loop {
+            self.line.clear();
+
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) => return None,
+                Ok(_) => self.line_number += 1,
+                Err(e) => return Some(Err(e.into())),
+            }
+
+            if let Some(record) = parse_line(self.line_number, &self.line) {
+                return Some(record);
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for RecordReader<R> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.mode {
+            Mode::Strict => self.read_record(),
+            Mode::Lenient => {
+                let mut skipped = std::mem::take(&mut self.skipped);
+                let record = skipped.next(|| self.read_record());
+                self.skipped = skipped;
+
+                record
+            }
+        }
+    }
+}
+
+/// Reader which decompresses its input if it is gzip compressed
+///
+/// Compression is detected from the gzip magic number at the start of the
+/// input, so plain extracts are read unchanged.
+#[cfg(feature = "gzip")]
+pub enum Decompress<R> {
+    Plain(R),
+    Gzip(BufReader<MultiGzDecoder<R>>),
+}
+
+/// The first two bytes of every gzip member
+#[cfg(feature = "gzip")]
+pub(crate) const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+#[cfg(feature = "gzip")]
+impl<R: BufRead> Decompress<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+            Ok(Decompress::Gzip(BufReader::new(MultiGzDecoder::new(
+                reader,
+            ))))
+        } else {
+            Ok(Decompress::Plain(reader))
+        }
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl<R: BufRead> Read for Decompress<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Decompress::Plain(reader) => reader.read(buf),
+            Decompress::Gzip(reader) => reader.read(buf),
+        }
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl<R: BufRead> BufRead for Decompress<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match self {
+            Decompress::Plain(reader) => reader.fill_buf(),
+            Decompress::Gzip(reader) => reader.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        match self {
+            Decompress::Plain(reader) => reader.consume(amt),
+            Decompress::Gzip(reader) => reader.consume(amt),
+        }
+    }
+}
+
+/// Groups records into train schedules as they are read
+#[derive(Default)]
+pub(crate) struct ScheduleGrouper {
+    current: Option<ScheduleBuilder>,
+}
+
+impl ScheduleGrouper {
+    /// Adds the next record, returning a schedule if the record completes one
+    pub(crate) fn push(&mut self, record: Record) -> Option<Result<TrainSchedule>> {
+        let record = match ScheduleRecord::try_from(record) {
+            Ok(record) => record,
+            // A schedule without locations, such as a cancellation, only ends
+            // when the next one starts
+            Err(Record::BS(bs)) => {
+                return self.current.replace(ScheduleBuilder::new(bs)).map(finish)
+            }
+            Err(_) => return self.finish(),
+        };
+
+        // A record which doesn't fit leaves the schedule as it was, to be
+        // completed by whatever follows
+        let pushed = self
+            .current
+            .as_mut()
+            .is_some_and(|builder| builder.push(record));
+
+        (!pushed).then(|| Err(Error::custom("schedule record found outside of a schedule")))
+    }
+
+    /// Completes the schedule being read at the end of the extract
+    pub(crate) fn finish(&mut self) -> Option<Result<TrainSchedule>> {
+        self.current.take().map(finish)
+    }
+}
+
+fn finish(builder: ScheduleBuilder) -> Result<TrainSchedule> {
+    builder.build().map_err(Error::missing_field)
+}
+
+/// Iterator over the train schedules of an extract, read one line at a time
+///
+/// Only the schedule being read is held in memory. Records which aren't part
+/// of a schedule, such as TIPLOCs and associations, are skipped.
+pub struct ScheduleReader<R> {
+    records: RecordReader<R>,
+    grouper: ScheduleGrouper,
+}
+
+impl<R: BufRead> ScheduleReader<R> {
+    pub fn new(records: RecordReader<R>) -> Self {
+        ScheduleReader {
+            records,
+            grouper: ScheduleGrouper::default(),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for ScheduleReader<R> {
+    type Item = Result<TrainSchedule>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let schedule = match self.records.next() {
+                Some(Ok(record)) => self.grouper.push(record),
+                Some(Err(e)) => return Some(Err(e)),
+                None => return self.grouper.finish(),
+            };
+
+            if schedule.is_some() {
+                return schedule;
+            }
+        }
+    }
+}
+
+/// A part of an extract, in the order the extract lists them
+///
+/// With the `serde` feature, an entry is serialized as an object with a
+/// `type` naming what it is, alongside the fields of the record or schedule.
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(tag = "type", rename_all = "snake_case")
+)]
+pub enum Entry {
+    Header(Header),
+    TiplocInsert(TiplocInsert),
+    TiplocAmend(TiplocAmend),
+    TiplocDelete(TiplocDelete),
+    Association(Association),
+    Schedule(Box<TrainSchedule>),
+    Trailer(Trailer),
+}
+
+impl Entry {
+    pub(crate) fn schedule(schedule: TrainSchedule) -> Self {
+        Entry::Schedule(Box::new(schedule))
+    }
+}
+
+/// Iterator over the entries of an extract, grouping the records of each
+/// train schedule
+///
+/// The extract must start with its header, and a header anywhere else is an
+/// error. A schedule is completed by the first record which isn't part of it,
+/// and is returned before that record. A schedule left incomplete when the
+/// records run out is reported as [Error::Incomplete]. Reading carries on
+/// past the trailer, so callers stop there themselves.
+pub struct Entries<I> {
+    records: I,
+    grouper: ScheduleGrouper,
+    /// Entry read while completing the schedule before it
+    pending: Option<Entry>,
+    started: bool,
+}
+
+impl<I: Iterator<Item = Result<Record>>> Entries<I> {
+    pub fn new(records: I) -> Self {
+        Entries {
+            records,
+            grouper: ScheduleGrouper::default(),
+            pending: None,
+            started: false,
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<Record>>> Iterator for Entries<I> {
+    type Item = Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(entry) = self.pending.take() {
+            return Some(Ok(entry));
+        }
+
+        loop {
+            let record = match self.records.next() {
+                Some(Ok(record)) => record,
+                Some(Err(e)) => return Some(Err(e)),
+                // Without the trailer, a schedule which can't be completed
+                // was cut short along with the extract
+                None => {
+                    return self.grouper.finish().map(|schedule| {
+                        schedule.map(Entry::schedule).map_err(|_| Error::Incomplete)
+                    })
+                }
+            };
+
+            let started = std::mem::replace(&mut self.started, true);
+
+            let entry = match record {
+                Record::HD(record) if !started => Entry::Header(record),
+                Record::HD(_) => {
+                    return Some(Err(Error::custom(
+                        "header record found after the start of the extract",
+                    )))
+                }
+                _ if !started => return Some(Err(Error::missing_field("header"))),
+                Record::TI(record) => Entry::TiplocInsert(record),
+                Record::TA(record) => Entry::TiplocAmend(record),
+                Record::TD(record) => Entry::TiplocDelete(record),
+                Record::AA(record) => Entry::Association(record),
+                Record::ZZ(record) => Entry::Trailer(record),
+                record => match self.grouper.push(record) {
+                    Some(schedule) => return Some(schedule.map(Entry::schedule)),
+                    None => continue,
+                },
+            };
+
+            return match self.grouper.finish() {
+                Some(schedule) => {
+                    self.pending = Some(entry);
+                    Some(schedule.map(Entry::schedule))
+                }
+                None => Some(Ok(entry)),
+            };
+        }
+    }
+}
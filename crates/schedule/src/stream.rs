@@ -0,0 +1,169 @@
+//! Reading extracts asynchronously, with the `tokio` feature
+//!
+//! [RecordStream] and [ScheduleStream] are the asynchronous counterparts of
+//! [RecordReader](crate::reader::RecordReader) and
+//! [ScheduleReader](crate::reader::ScheduleReader), reading from any
+//! [AsyncBufRead] a line at a time. With the `gzip` feature as well,
+//! [AsyncDecompress] reads extracts which may or may not be gzip compressed.
+
+use std::{
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+#[cfg(feature = "gzip")]
+use async_compression::tokio::bufread::GzipDecoder;
+use futures_core::Stream;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, Lines};
+#[cfg(feature = "gzip")]
+use tokio::io::{AsyncRead, BufReader, ReadBuf};
+
+use crate::{
+    error::Result,
+    extract::Record,
+    reader::{parse_line, ScheduleGrouper},
+    timetable::TrainSchedule,
+};
+
+/// Stream of the records of an extract, read one line at a time
+pub struct RecordStream<R> {
+    lines: Lines<R>,
+    line_number: usize,
+}
+
+impl<R: AsyncBufRead + Unpin> RecordStream<R> {
+    pub fn new(reader: R) -> Self {
+        RecordStream {
+            lines: reader.lines(),
+            line_number: 0,
+        }
+    }
+
+    /// Groups the records into train schedules, skipping every other record
+    pub fn schedules(self) -> ScheduleStream<R> {
+        ScheduleStream::new(self)
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> Stream for RecordStream<R> {
+    type Item = Result<Record>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let Some(line) = ready!(Pin::new(&mut self.lines).poll_next_line(cx))? else {
+                return Poll::Ready(None);
+            };
+
+            self.line_number += 1;
+
+            if let Some(record) = parse_line(self.line_number, &line) {
+                return Poll::Ready(Some(record));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl<R: AsyncBufRead + Unpin> RecordStream<AsyncDecompress<R>> {
+    /// Reads an extract which may be gzip compressed, decompressing it as it
+    /// is read if so
+    pub async fn decompress(reader: R) -> std::io::Result<Self> {
+        Ok(RecordStream::new(AsyncDecompress::new(reader).await?))
+    }
+}
+
+/// Asynchronous reader which decompresses its input if it is gzip compressed
+///
+/// The asynchronous counterpart of [Decompress](crate::reader::Decompress).
+#[cfg(feature = "gzip")]
+pub enum AsyncDecompress<R> {
+    Plain(R),
+    Gzip(BufReader<GzipDecoder<R>>),
+}
+
+#[cfg(feature = "gzip")]
+impl<R: AsyncBufRead + Unpin> AsyncDecompress<R> {
+    pub async fn new(mut reader: R) -> std::io::Result<Self> {
+        if reader
+            .fill_buf()
+            .await?
+            .starts_with(&crate::reader::GZIP_MAGIC)
+        {
+            let mut decoder = GzipDecoder::new(reader);
+            decoder.multiple_members(true);
+
+            Ok(AsyncDecompress::Gzip(BufReader::new(decoder)))
+        } else {
+            Ok(AsyncDecompress::Plain(reader))
+        }
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl<R: AsyncBufRead + Unpin> AsyncRead for AsyncDecompress<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            AsyncDecompress::Plain(reader) => Pin::new(reader).poll_read(cx, buf),
+            AsyncDecompress::Gzip(reader) => Pin::new(reader).poll_read(cx, buf),
+        }
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl<R: AsyncBufRead + Unpin> AsyncBufRead for AsyncDecompress<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<&[u8]>> {
+        match self.get_mut() {
+            AsyncDecompress::Plain(reader) => Pin::new(reader).poll_fill_buf(cx),
+            AsyncDecompress::Gzip(reader) => Pin::new(reader).poll_fill_buf(cx),
+        }
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        match self.get_mut() {
+            AsyncDecompress::Plain(reader) => Pin::new(reader).consume(amt),
+            AsyncDecompress::Gzip(reader) => Pin::new(reader).consume(amt),
+        }
+    }
+}
+
+/// Stream of the train schedules of an extract, read one line at a time
+///
+/// Records which aren't part of a schedule, such as TIPLOCs and associations,
+/// are skipped.
+pub struct ScheduleStream<R> {
+    records: RecordStream<R>,
+    grouper: ScheduleGrouper,
+}
+
+impl<R: AsyncBufRead + Unpin> ScheduleStream<R> {
+    pub fn new(records: RecordStream<R>) -> Self {
+        ScheduleStream {
+            records,
+            grouper: ScheduleGrouper::default(),
+        }
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> Stream for ScheduleStream<R> {
+    type Item = Result<TrainSchedule>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        loop {
+            let schedule = match ready!(Pin::new(&mut this.records).poll_next(cx)) {
+                Some(Ok(record)) => this.grouper.push(record),
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => return Poll::Ready(this.grouper.finish()),
+            };
+
+            if schedule.is_some() {
+                return Poll::Ready(schedule);
+            }
+        }
+    }
+}
@@ -1,31 +1,132 @@
-use serde::{
-    de::{self, Visitor},
-    Deserialize,
-};
+use std::{io::BufRead, str::FromStr};
+
+pub mod sequence;
+pub mod store;
+pub mod stp;
+
+pub use sequence::*;
+pub use store::*;
+pub use stp::*;
 
-use crate::extract::{
-    header::Header, tiploc::TiplocInsert, BasicSchedule, BasicScheduleExtra, IntermediateLocation,
-    OriginLocation, TerminatingLocation, TiplocAmend, TiplocDelete,
+use crate::{
+    error::{Diagnostic, Error, Result},
+    extract::{
+        header::Header, tiploc::TiplocInsert, Allowance, Association, BasicSchedule,
+        BasicScheduleExtra, Catering, ChangeEnRoute, IntermediateLocation, OperatingCharacteristic,
+        OriginLocation, PowerType, Record, Reservations, SeatingClass, Sleepers, StpIndicator,
+        TerminatingLocation, TimingLoad, Tiploc, TiplocAmend, TiplocDelete, TrainCategory,
+        TransactionType,
+    },
+    reader::{Entries, Entry, RecordReader, Skipped},
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 /// Type representing a timetable
 ///
 /// To be a valid table, the input must contain a minimum of a header and a trailer
-/// record type.
+/// record type. A missing trailer is reported as [crate::error::Error::Incomplete].
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Timetable {
     pub header: Header,
     pub tiploc_inserts: Vec<TiplocInsert>,
     pub tiploc_amends: Vec<TiplocAmend>,
     pub tiploc_deletes: Vec<TiplocDelete>,
+    pub associations: Vec<Association>,
     pub train_schedules: Vec<TrainSchedule>,
 }
 
-impl<'de> Deserialize<'de> for Timetable {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+impl Timetable {
+    /// Builds a timetable from the records of an extract, in the order they
+    /// were read
+    ///
+    /// The first record must be the header, and the only one. Reading stops at
+    /// the trailer, and the first error from `records` is returned as it is.
+    pub fn from_records<I>(records: I) -> Result<Timetable>
+    where
+        I: IntoIterator<Item = Result<Record>>,
+    {
+        let mut entries = Entries::new(records.into_iter());
+
+        let header = match entries.next().transpose()? {
+            Some(Entry::Header(header)) => header,
+            _ => return Err(Error::missing_field("header")),
+        };
+
+        let mut timetable = Timetable {
+            header,
+            tiploc_inserts: Vec::new(),
+            tiploc_amends: Vec::new(),
+            tiploc_deletes: Vec::new(),
+            associations: Vec::new(),
+            train_schedules: Vec::new(),
+        };
+
+        for entry in entries {
+            match entry? {
+                Entry::TiplocInsert(record) => timetable.tiploc_inserts.push(record),
+                Entry::TiplocAmend(record) => timetable.tiploc_amends.push(record),
+                Entry::TiplocDelete(record) => timetable.tiploc_deletes.push(record),
+                Entry::Association(record) => timetable.associations.push(record),
+                Entry::Schedule(schedule) => timetable.train_schedules.push(*schedule),
+                Entry::Trailer(_) => return Ok(timetable),
+                // Entries only returns a header first
+                Entry::Header(_) => unreachable!(),
+            }
+        }
+
+        Err(Error::Incomplete)
+    }
+
+    /// Builds a timetable from the records of an extract, skipping malformed
+    /// records as [Mode::Lenient](crate::Mode::Lenient) does
+    ///
+    /// Returns a [Diagnostic] for each record skipped. A train schedule which
+    /// can't be built without its malformed records is skipped as a whole.
+    /// Errors which aren't confined to malformed records, such as a schedule
+    /// without an origin location at all, still fail, as
+    /// [Error::WithDiagnostics] when records were skipped before them.
+    pub fn from_records_lenient<I>(records: I) -> Result<(Timetable, Vec<Diagnostic>)>
+    where
+        I: IntoIterator<Item = Result<Record>>,
+    {
+        let mut skipped = Skipped::default();
+        let mut records = records.into_iter();
+        let records = std::iter::from_fn(|| skipped.next(|| records.next()));
+
+        match Timetable::from_records(records) {
+            Ok(timetable) => Ok((timetable, skipped.diagnostics)),
+            Err(error) => Err(error.with_diagnostics(skipped.diagnostics)),
+        }
+    }
+
+    /// Reads a timetable from an extract one line at a time
+    ///
+    /// With the `gzip` feature, compressed extracts are decompressed as they
+    /// are read.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Timetable> {
+        #[cfg(feature = "gzip")]
+        let reader = crate::reader::Decompress::new(reader)?;
+
+        Timetable::from_records(RecordReader::new(reader))
+    }
+}
+
+impl FromStr for Timetable {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Timetable::from_reader(s.as_bytes())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Timetable {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
+        use serde::de::{self, Visitor};
+
         struct TimetableVisitor;
 
         impl<'de> Visitor<'de> for TimetableVisitor {
@@ -35,37 +136,29 @@ impl<'de> Deserialize<'de> for Timetable {
                 write!(formatter, "a sequence of one or more records")
             }
 
-            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Self::Value, A::Error>
             where
                 A: serde::de::SeqAccess<'de>,
             {
-                let header: Header = seq
-                    .next_element()?
-                    .ok_or_else(|| de::Error::invalid_length(0, &self))?;
-
-                let tiploc_inserts: Vec<TiplocInsert> = seq
-                    .next_element()?
-                    .ok_or_else(|| serde::de::Error::invalid_length(1, &self))?;
+                // Records are grouped the same way as when they are read
+                // without serde, keeping the error from the sequence itself
+                let mut error = None;
+                let records = std::iter::from_fn(|| match seq.next_element::<Record>() {
+                    Ok(record) => record.map(Ok),
+                    Err(e) => {
+                        error = Some(e);
+                        None
+                    }
+                });
 
-                let tiploc_amends: Vec<TiplocAmend> = seq
-                    .next_element()?
-                    .ok_or_else(|| serde::de::Error::invalid_length(2, &self))?;
+                let timetable = Timetable::from_records(records);
 
-                let tiploc_deletes: Vec<TiplocDelete> = seq
-                    .next_element()?
-                    .ok_or_else(|| serde::de::Error::invalid_length(3, &self))?;
-
-                let train_schedules: Vec<TrainSchedule> = seq
-                    .next_element()?
-                    .ok_or_else(|| serde::de::Error::invalid_length(4, &self))?;
-
-                Ok(Timetable {
-                    header,
-                    tiploc_inserts,
-                    tiploc_amends,
-                    tiploc_deletes,
-                    train_schedules,
-                })
+                // A missing trailer is told apart by the deserializer, which
+                // knows whether the input ran out
+                match error {
+                    Some(error) => Err(error),
+                    None => timetable.map_err(de::Error::custom),
+                }
             }
         }
 
@@ -73,20 +166,376 @@ impl<'de> Deserialize<'de> for Timetable {
     }
 }
 
-#[derive(Debug)]
+/// Type representing a single train's schedule and calling pattern
+///
+/// STP cancellations and deletions are made up of the basic schedule alone,
+/// so the extra details and the origin and terminating locations are only
+/// absent for those.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct TrainSchedule {
+    pub bs: BasicSchedule,
+    pub bsx: Option<BasicScheduleExtra>,
+    pub origin_location: Option<OriginLocation>,
+    pub intermediate_locations: Vec<IntermediateLocation>,
+    pub terminating_location: Option<TerminatingLocation>,
+    /// Changes en route, in the order they appear in the calling pattern
+    pub changes_en_route: Vec<ChangeEnRouteAt>,
+    /// Train notes (TN records), in the order they appear
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_notes"))]
+    pub train_notes: Vec<String>,
+    /// Location notes (LN records), in the order they appear in the calling
+    /// pattern
+    pub location_notes: Vec<LocationNoteAt>,
+}
+
+/// A change en route, along with the location it applies from
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ChangeEnRouteAt {
+    /// Index of the intermediate location the change appears before in the
+    /// calling pattern, counting the origin location as `0` as
+    /// [TrainSchedule::attributes_at] does
+    pub location_index: usize,
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub change: ChangeEnRoute,
+}
+
+/// A location note, along with the location it is given for
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LocationNoteAt {
+    /// Index of the location the note follows in the calling pattern,
+    /// counting the origin location as `0` as [TrainSchedule::attributes_at]
+    /// does
+    pub location_index: usize,
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "crate::extract::serialize_text")
+    )]
+    pub note: String,
+}
+
+/// Serializes notes without their padding, in the same way as other text
+#[cfg(feature = "serde")]
+fn serialize_notes<S: serde::Serializer>(
+    notes: &[String],
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_seq(notes.iter().map(|note| note.trim()))
+}
+
+/// Attributes of a train which may change part way along its route
+#[derive(Debug, Clone)]
+pub struct TrainAttributes {
+    pub train_category: Option<TrainCategory>,
+    pub train_identity: Option<String>,
+    pub headcode: Option<String>,
+    pub course_indicator: Option<u8>,
+    pub train_service_code: Option<String>,
+    pub portion_id: Option<char>,
+    pub power_type: Option<PowerType>,
+    pub timing_load: Option<TimingLoad>,
+    pub speed: Option<u16>,
+    pub operating_chars: Vec<OperatingCharacteristic>,
+    pub seating_class: Option<SeatingClass>,
+    pub sleepers: Option<Sleepers>,
+    pub reservations: Option<Reservations>,
+    pub connection_indicator: Option<char>,
+    pub catering: Vec<Catering>,
+    pub service_branding: Option<String>,
+    pub uic_code: Option<String>,
+    pub retail_service_id: Option<String>,
+}
+
+impl TrainAttributes {
+    fn new(bs: &BasicSchedule, bsx: Option<&BasicScheduleExtra>) -> Self {
+        TrainAttributes {
+            train_category: bs.train_category.clone(),
+            train_identity: bs.train_identity.clone(),
+            headcode: bs.headcode.clone(),
+            course_indicator: bs.course_indicator,
+            train_service_code: bs.train_service_code.clone(),
+            portion_id: bs.portion_id,
+            power_type: bs.power_type.clone(),
+            timing_load: bs.timing_load.clone(),
+            speed: bs.speed,
+            operating_chars: bs.operating_chars.clone(),
+            seating_class: bs.seating_class.clone(),
+            sleepers: bs.sleepers.clone(),
+            reservations: bs.reservations.clone(),
+            connection_indicator: bs.connection_indicator,
+            catering: bs.catering.clone(),
+            service_branding: bs.service_branding.clone(),
+            uic_code: bsx.and_then(|bsx| bsx.uic_code.clone()),
+            retail_service_id: bsx.and_then(|bsx| bsx.retail_service_id.clone()),
+        }
+    }
+
+    /// Replaces every attribute with the values given by a change en route
+    fn apply(&mut self, cr: &ChangeEnRoute) {
+        *self = TrainAttributes {
+            train_category: cr.train_category.clone(),
+            train_identity: cr.train_identity.clone(),
+            headcode: cr.headcode.clone(),
+            course_indicator: cr.course_indicator,
+            train_service_code: cr.train_service_code.clone(),
+            portion_id: cr.portion_id,
+            power_type: cr.power_type.clone(),
+            timing_load: cr.timing_load.clone(),
+            speed: cr.speed,
+            operating_chars: cr.operating_chars.clone(),
+            seating_class: cr.seating_class.clone(),
+            sleepers: cr.sleepers.clone(),
+            reservations: cr.reservations.clone(),
+            connection_indicator: cr.connection_indicator,
+            catering: cr.catering.clone(),
+            service_branding: cr.service_branding.clone(),
+            uic_code: cr.uic_code.clone(),
+            retail_service_id: cr.retail_service_id.clone(),
+        };
+    }
+}
+
+impl TrainSchedule {
+    /// Number of locations in the calling pattern, including the origin and
+    /// terminating locations
+    pub fn location_count(&self) -> usize {
+        self.tiplocs().count()
+    }
+
+    /// Returns the attributes the train has at a location in its calling
+    /// pattern, taking any changes en route into account
+    ///
+    /// The index counts from the origin location at `0`, through each
+    /// intermediate location, to the terminating location. Returns `None` if
+    /// the index is past the end of the calling pattern.
+    pub fn attributes_at(&self, index: usize) -> Option<TrainAttributes> {
+        if index >= self.location_count() {
+            return None;
+        }
+
+        let mut attributes = TrainAttributes::new(&self.bs, self.bsx.as_ref());
+
+        for cr in self
+            .changes_en_route
+            .iter()
+            .take_while(|cr| cr.location_index <= index)
+        {
+            attributes.apply(&cr.change);
+        }
+
+        Some(attributes)
+    }
+
+    /// TIPLOCs of the calling pattern in order, from the origin location to
+    /// the terminating location
+    fn tiplocs(&self) -> impl Iterator<Item = &Tiploc> {
+        self.origin_location
+            .iter()
+            .map(|l| &l.location)
+            .chain(self.intermediate_locations.iter().map(|l| &l.location))
+            .chain(self.terminating_location.iter().map(|l| &l.location))
+    }
+
+    /// Totals the allowances between two locations in the calling pattern
+    ///
+    /// An allowance is given against the location it is taken after, so the
+    /// allowances at `from` are included and those at `to` are not. Returns
+    /// `None` unless `to` is called at or passed after `from`.
+    pub fn allowances_between(&self, from: &str, to: &str) -> Option<Allowances> {
+        let start = self.tiplocs().position(|tiploc| &**tiploc == from)?;
+        let end = start
+            + self
+                .tiplocs()
+                .skip(start)
+                .position(|tiploc| &**tiploc == to)?;
+
+        let mut allowances = Allowances::default();
+
+        if let Some(origin) = self
+            .origin_location
+            .as_ref()
+            .filter(|_| start == 0 && end > 0)
+        {
+            allowances.add(
+                origin.engineering_allowance,
+                origin.pathing_allowance,
+                origin.performance_allowance,
+            );
+        }
+
+        // Intermediate locations start at index 1 of the calling pattern
+        let first = start.saturating_sub(1);
+        let last = end.saturating_sub(1);
+
+        for location in &self.intermediate_locations[first..last] {
+            allowances.add(
+                location.engineering_allowance,
+                location.pathing_allowance,
+                location.performance_allowance,
+            );
+        }
+
+        Some(allowances)
+    }
+}
+
+/// Totals of each kind of allowance over part of a schedule
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Allowances {
+    pub engineering: Allowance,
+    pub pathing: Allowance,
+    pub performance: Allowance,
+}
+
+impl Allowances {
+    fn add(
+        &mut self,
+        engineering: Option<Allowance>,
+        pathing: Option<Allowance>,
+        performance: Option<Allowance>,
+    ) {
+        self.engineering = self.engineering + engineering.unwrap_or_default();
+        self.pathing = self.pathing + pathing.unwrap_or_default();
+        self.performance = self.performance + performance.unwrap_or_default();
+    }
+
+    /// Sum of every kind of allowance
+    pub fn total(&self) -> Allowance {
+        self.engineering + self.pathing + self.performance
+    }
+}
+
+/// Records which may follow a basic schedule as part of the same train
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub(crate) enum ScheduleRecord {
+    BX(BasicScheduleExtra),
+    LO(OriginLocation),
+    LI(IntermediateLocation),
+    CR(ChangeEnRoute),
+    LT(TerminatingLocation),
+    TN(String),
+    LN(String),
+}
+
+impl TryFrom<Record> for ScheduleRecord {
+    type Error = Record;
+
+    /// Takes a record which may follow a basic schedule, giving back any other
+    fn try_from(record: Record) -> std::result::Result<Self, Record> {
+        Ok(match record {
+            Record::BX(record) => ScheduleRecord::BX(record),
+            Record::LO(record) => ScheduleRecord::LO(record),
+            Record::LI(record) => ScheduleRecord::LI(record),
+            Record::CR(record) => ScheduleRecord::CR(record),
+            Record::LT(record) => ScheduleRecord::LT(record),
+            Record::TN(note) => ScheduleRecord::TN(note),
+            Record::LN(note) => ScheduleRecord::LN(note),
+            record => return Err(record),
+        })
+    }
+}
+
+/// A train schedule part way through being read
+pub(crate) struct ScheduleBuilder {
     bs: BasicSchedule,
-    bsx: BasicScheduleExtra,
-    origin_location: OriginLocation,
+    bsx: Option<BasicScheduleExtra>,
+    origin_location: Option<OriginLocation>,
     intermediate_locations: Vec<IntermediateLocation>,
-    terminating_location: TerminatingLocation,
+    changes_en_route: Vec<ChangeEnRouteAt>,
+    terminating_location: Option<TerminatingLocation>,
+    train_notes: Vec<String>,
+    location_notes: Vec<LocationNoteAt>,
+}
+
+impl ScheduleBuilder {
+    pub(crate) fn new(bs: BasicSchedule) -> Self {
+        ScheduleBuilder {
+            bs,
+            bsx: None,
+            origin_location: None,
+            intermediate_locations: Vec::new(),
+            changes_en_route: Vec::new(),
+            terminating_location: None,
+            train_notes: Vec::new(),
+            location_notes: Vec::new(),
+        }
+    }
+
+    /// Adds a record to the schedule, returning `false` if it can't be part
+    /// of it
+    ///
+    /// The schedule isn't complete at its terminating location, as location
+    /// notes may still follow it.
+    pub(crate) fn push(&mut self, record: ScheduleRecord) -> bool {
+        if self.terminating_location.is_some() && !matches!(record, ScheduleRecord::LN(_)) {
+            return false;
+        }
+
+        match record {
+            ScheduleRecord::BX(record) => self.bsx = Some(record),
+            ScheduleRecord::LO(record) => self.origin_location = Some(record),
+            ScheduleRecord::LI(record) => self.intermediate_locations.push(record),
+            // A change appears directly before the intermediate location it
+            // applies from, which comes after the origin at index 0
+            ScheduleRecord::CR(record) => self.changes_en_route.push(ChangeEnRouteAt {
+                location_index: self.intermediate_locations.len() + 1,
+                change: record,
+            }),
+            ScheduleRecord::LT(record) => self.terminating_location = Some(record),
+            ScheduleRecord::TN(note) => self.train_notes.push(note),
+            // A note appears directly after the location it is given for
+            ScheduleRecord::LN(note) => self.location_notes.push(LocationNoteAt {
+                location_index: self.intermediate_locations.len()
+                    + usize::from(self.terminating_location.is_some()),
+                note,
+            }),
+        }
+
+        true
+    }
+
+    /// Completes the schedule, or names the first record it is missing
+    pub(crate) fn build(self) -> std::result::Result<TrainSchedule, &'static str> {
+        // Only cancellations and deletions may stand on their own
+        if self.bs.stp_indicator != StpIndicator::Cancellation
+            && self.bs.transaction_type != TransactionType::Delete
+        {
+            if self.bsx.is_none() {
+                return Err("bsx");
+            }
+
+            if self.origin_location.is_none() {
+                return Err("origin_location");
+            }
+
+            if self.terminating_location.is_none() {
+                return Err("terminating_location");
+            }
+        }
+
+        Ok(TrainSchedule {
+            bs: self.bs,
+            bsx: self.bsx,
+            origin_location: self.origin_location,
+            intermediate_locations: self.intermediate_locations,
+            terminating_location: self.terminating_location,
+            changes_en_route: self.changes_en_route,
+            train_notes: self.train_notes,
+            location_notes: self.location_notes,
+        })
+    }
 }
 
-impl<'de> Deserialize<'de> for TrainSchedule {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for TrainSchedule {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
     where
-        D: de::Deserializer<'de>,
+        D: serde::Deserializer<'de>,
     {
+        use serde::de::{self, Visitor};
+
         struct TrainScheduleVisitor;
 
         impl<'de> Visitor<'de> for TrainScheduleVisitor {
@@ -96,7 +545,7 @@ impl<'de> Deserialize<'de> for TrainSchedule {
                 write!(formatter, "struct TrainSchedule")
             }
 
-            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Self::Value, A::Error>
             where
                 A: de::SeqAccess<'de>,
             {
@@ -104,29 +553,17 @@ impl<'de> Deserialize<'de> for TrainSchedule {
                     .next_element()?
                     .ok_or_else(|| de::Error::invalid_length(0, &self))?;
 
-                let bsx = seq
-                    .next_element()?
-                    .ok_or_else(|| de::Error::invalid_length(1, &self))?;
-
-                let origin_location = seq
-                    .next_element()?
-                    .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+                let mut builder = ScheduleBuilder::new(bs);
 
-                let intermediate_locations: Vec<IntermediateLocation> = seq
-                    .next_element()?
-                    .ok_or_else(|| de::Error::invalid_length(3, &self))?;
+                while let Some(record) = seq.next_element()? {
+                    if !builder.push(record) {
+                        return Err(de::Error::custom(
+                            "schedule record found outside of a schedule",
+                        ));
+                    }
+                }
 
-                let terminating_location = seq
-                    .next_element()?
-                    .ok_or_else(|| de::Error::invalid_length(4, &self))?;
-
-                Ok(TrainSchedule {
-                    bs,
-                    bsx,
-                    origin_location,
-                    intermediate_locations,
-                    terminating_location,
-                })
+                builder.build().map_err(de::Error::missing_field)
             }
         }
 
@@ -134,9 +571,29 @@ impl<'de> Deserialize<'de> for TrainSchedule {
     }
 }
 
+#[cfg(test)]
 mod test {
+    use crate::{error, reader::RecordReader};
+
+    use super::TrainSchedule;
+
+    /// Reads a schedule with each parser, so the same assertions cover both
+    fn read_schedules(raw: &str) -> error::Result<Vec<TrainSchedule>> {
+        let schedule = RecordReader::new(raw.as_bytes())
+            .schedules()
+            .next()
+            .expect("a schedule")?;
+
+        #[allow(unused_mut)]
+        let mut schedules = vec![schedule];
 
-    const SCHEDULE: &'static str = r#"BSNP132082412152505110000001 PXX1S758044121730001 EMU397 125      B A F        P
+        #[cfg(feature = "serde")]
+        schedules.push(crate::from_str(raw)?);
+
+        Ok(schedules)
+    }
+
+    const SCHEDULE: &str = r#"BSNP132082412152505110000001 PXX1S758044121730001 EMU397 125      B A F        P
 BX         TPY                                                                  
 LOMNCRIAP 1610 16104A        TB                                                 
 LIHLDGWJ            1612 00000000                                               
@@ -183,13 +640,121 @@ LIMDCLDRJ           1921 00000000                     1   1H
 LISLATEFD           1930 00000000                                               
 LIHAYMRKT 1932H1934      193300003  US    D              H                      
 LIPRNCSTG           1936H00000000   X                                           
+LTEDINBUR 1939 193912 X  TF                                                     "#;
+
+    const SCHEDULE_WITH_CHANGE: &str = r#"BSNP132082412152505110000001 PXX1S758044121730001 EMU397 125      B A F        P
+BX         TPY                                                                  
+LOMNCRIAP 1610 16104A        TB                                                 
+LIBOLTON  1642H1644      000016444        U                                     
+CRPRST    OO2P12    121700001 DMU150 075      S                                 
+LIPRST    1703 1705H     170317053  DFLDFLT                                     
 LTEDINBUR 1939 193912 X  TF                                                     "#;
 
     #[test]
-    pub fn test_schedule() -> Result<(), Box<dyn std::error::Error>> {
-        let schedule: super::TrainSchedule = crate::from_str(SCHEDULE)?;
+    pub fn test_change_en_route() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::extract::PowerType;
+
+        for schedule in read_schedules(SCHEDULE_WITH_CHANGE)? {
+            assert_eq!(schedule.changes_en_route.len(), 1);
+            assert_eq!(schedule.intermediate_locations.len(), 2);
+
+            let bolton = schedule.attributes_at(1).unwrap();
+            assert_eq!(bolton.power_type, Some(PowerType::ElectricMultipleUnit));
+
+            let preston = schedule.attributes_at(2).unwrap();
+            assert_eq!(
+                preston.power_type,
+                Some(PowerType::DieselMechanicalMultipleUnit)
+            );
+            assert_eq!(preston.train_identity.as_deref(), Some("2P12"));
+
+            let terminus = schedule.attributes_at(3).unwrap();
+            assert_eq!(terminus.speed, Some(75));
+
+            assert!(schedule.attributes_at(4).is_none());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_notes() -> Result<(), Box<dyn std::error::Error>> {
+        let raw = format!(
+            "{}\n{}",
+            SCHEDULE_WITH_CHANGE.replace(
+                "\nLO",
+                "\nTNA note for the whole train                                                    \nLO",
+            ),
+            "LNA note for the terminating location                                           ",
+        );
+
+        for schedule in read_schedules(&raw)? {
+            assert_eq!(schedule.train_notes.len(), 1);
+            assert_eq!(schedule.location_notes.len(), 1);
+            assert_eq!(schedule.location_notes[0].location_index, 3);
+            assert_eq!(crate::to_string(&schedule), raw);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_unmatched_change_en_route() -> Result<(), Box<dyn std::error::Error>> {
+        // The first change names a different TIPLOC to the location it is
+        // before, which mustn't stop the second from being placed
+        let raw = SCHEDULE_WITH_CHANGE.replace(
+            "LIBOLTON  ",
+            "CRELSEWHR OO2P11    121700001 EMU397 100      S                                 \nLIBOLTON  ",
+        );
+
+        for schedule in read_schedules(&raw)? {
+            let indices: Vec<_> = schedule
+                .changes_en_route
+                .iter()
+                .map(|cr| cr.location_index)
+                .collect();
+            assert_eq!(indices, [1, 2]);
+
+            assert_eq!(schedule.attributes_at(0).unwrap().speed, Some(125));
+            assert_eq!(schedule.attributes_at(1).unwrap().speed, Some(100));
+            assert_eq!(schedule.attributes_at(2).unwrap().speed, Some(75));
+            assert_eq!(crate::to_string(&schedule), raw);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_allowances_between() -> Result<(), Box<dyn std::error::Error>> {
+        for schedule in read_schedules(SCHEDULE)? {
+            let whole = schedule.allowances_between("MNCRIAP", "EDINBUR").unwrap();
+            assert_eq!(whole.engineering.to_string(), "8");
+            assert_eq!(whole.pathing.to_string(), "4H");
+            assert_eq!(whole.performance.to_string(), "1H");
+            assert_eq!(whole.total().to_string(), "14");
+
+            let section = schedule.allowances_between("HLDG", "ARDWCKJ").unwrap();
+            assert_eq!(section.pathing.to_string(), "1");
+
+            assert!(schedule.allowances_between("EDINBUR", "MNCRIAP").is_none());
+        }
+
+        Ok(())
+    }
 
-        println!("{schedule:?}");
+    #[test]
+    pub fn test_schedule() -> Result<(), Box<dyn std::error::Error>> {
+        for schedule in read_schedules(SCHEDULE)? {
+            assert_eq!(
+                schedule.origin_location.as_ref().map(|l| &*l.location),
+                Some("MNCRIAP")
+            );
+            assert_eq!(schedule.intermediate_locations.len(), 44);
+            assert_eq!(
+                schedule.terminating_location.as_ref().map(|l| &*l.location),
+                Some("EDINBUR")
+            );
+        }
 
         Ok(())
     }
@@ -0,0 +1,157 @@
+use chrono::{NaiveDate, TimeDelta};
+use thiserror::Error;
+
+use crate::extract::{ExtractType, Header};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SequenceError {
+    #[error("an update cannot be applied before a full extract")]
+    NoFullExtract,
+    #[error("extract {0} has already been applied")]
+    Duplicate(String),
+    #[error("extract {found} is older than the last applied extract {last}")]
+    OutOfOrder { last: String, found: String },
+    #[error("extract follows on from {follows}, but the last applied extract was {last}")]
+    Gap { last: String, follows: String },
+    #[error("extract should start on {expected}, but starts on {found}")]
+    DateGap {
+        expected: NaiveDate,
+        found: NaiveDate,
+    },
+}
+
+/// The last extract applied to a timetable
+#[derive(Debug, Clone)]
+struct Applied {
+    file_ref: String,
+    start: NaiveDate,
+}
+
+/// Checks that update extracts are applied one after another, without any
+/// being missed, repeated or applied out of order
+///
+/// Each update names the file it follows on from in its
+/// [last file reference](Header::last_file_ref), and covers the day after
+/// the one before it. A full extract starts a new sequence.
+#[derive(Debug, Clone, Default)]
+pub struct UpdateSequence {
+    last: Option<Applied>,
+}
+
+impl UpdateSequence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reference of the last extract applied, if there has been one
+    pub fn last_file_ref(&self) -> Option<&str> {
+        self.last.as_ref().map(|last| last.file_ref.as_str())
+    }
+
+    /// Checks whether the extract can be applied next, without recording it
+    pub fn check(&self, header: &Header) -> Result<(), SequenceError> {
+        let start = header.extract_start_date;
+
+        if let ExtractType::Full = header.extract_type {
+            return Ok(());
+        }
+
+        let last = self.last.as_ref().ok_or(SequenceError::NoFullExtract)?;
+
+        if header.current_file_ref == last.file_ref {
+            return Err(SequenceError::Duplicate(header.current_file_ref.clone()));
+        }
+
+        if start <= last.start {
+            return Err(SequenceError::OutOfOrder {
+                last: last.file_ref.clone(),
+                found: header.current_file_ref.clone(),
+            });
+        }
+
+        if header.last_file_ref != last.file_ref {
+            return Err(SequenceError::Gap {
+                last: last.file_ref.clone(),
+                follows: header.last_file_ref.clone(),
+            });
+        }
+
+        let expected = last.start + TimeDelta::days(1);
+
+        if start != expected {
+            return Err(SequenceError::DateGap {
+                expected,
+                found: start,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Checks the extract can be applied next, and records it as the last
+    /// applied if so
+    pub fn advance(&mut self, header: &Header) -> Result<(), SequenceError> {
+        self.check(header)?;
+
+        self.last = Some(Applied {
+            file_ref: header.current_file_ref.clone(),
+            start: header.extract_start_date,
+        });
+
+        Ok(())
+    }
+}
+
+#[test]
+fn update_sequence() -> Result<(), Box<dyn std::error::Error>> {
+    use std::str::FromStr;
+
+    let header = |current: &str, last: &str, extract_type: &str, start: &str| {
+        let raw = format!(
+            "HDTPS.UDFROC1.PD2502282802252154{current}{last}{extract_type}A{start}280226{:20}",
+            ""
+        );
+
+        Header::from_str(&raw)
+    };
+
+    let full = header("DFROC1B", "       ", "F", "280225")?;
+    let first = header("DFROC1C", "DFROC1B", "U", "010325")?;
+    let second = header("DFROC1D", "DFROC1C", "U", "020325")?;
+    let third = header("DFROC1E", "DFROC1D", "U", "030325")?;
+
+    let mut sequence = UpdateSequence::new();
+
+    assert_eq!(sequence.check(&first), Err(SequenceError::NoFullExtract));
+
+    sequence.advance(&full)?;
+    sequence.advance(&first)?;
+
+    assert_eq!(
+        sequence.check(&first),
+        Err(SequenceError::Duplicate("DFROC1C".to_string()))
+    );
+    assert_eq!(
+        sequence.check(&third),
+        Err(SequenceError::Gap {
+            last: "DFROC1C".to_string(),
+            follows: "DFROC1D".to_string()
+        })
+    );
+
+    sequence.advance(&second)?;
+
+    assert_eq!(sequence.last_file_ref(), Some("DFROC1D"));
+    assert!(matches!(
+        sequence.check(&first),
+        Err(SequenceError::OutOfOrder { .. })
+    ));
+
+    let late = header("DFROC1E", "DFROC1D", "U", "050325")?;
+    assert!(matches!(
+        sequence.check(&late),
+        Err(SequenceError::DateGap { .. })
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,538 @@
+use std::{collections::HashMap, fmt, hash::Hash};
+
+use chrono::NaiveDate;
+use thiserror::Error;
+
+use crate::{
+    error::Error,
+    extract::{
+        Association, ExtractType, Record, StpIndicator, Tiploc, TiplocInsert, TransactionType,
+    },
+    reader::{Entries, Entry},
+};
+
+use super::{SequenceError, Timetable, TrainSchedule, UpdateSequence};
+
+/// Identifies a schedule between extracts, as used by revisions and deletions
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ScheduleKey {
+    pub train_uid: String,
+    pub from: NaiveDate,
+    pub stp_indicator: StpIndicator,
+}
+
+impl ScheduleKey {
+    fn new(schedule: &TrainSchedule) -> Self {
+        ScheduleKey {
+            train_uid: schedule.bs.train_uid.clone(),
+            from: schedule.bs.from,
+            stp_indicator: schedule.bs.stp_indicator,
+        }
+    }
+}
+
+impl fmt::Display for ScheduleKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} from {} ({:?})",
+            self.train_uid, self.from, self.stp_indicator
+        )
+    }
+}
+
+/// Identifies an association between extracts, as used by revisions and
+/// deletions
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AssociationKey {
+    pub base_uid: String,
+    pub associated_uid: String,
+    pub from: NaiveDate,
+    pub location: Tiploc,
+    pub stp_indicator: StpIndicator,
+}
+
+impl AssociationKey {
+    fn new(association: &Association) -> Self {
+        AssociationKey {
+            base_uid: association.base_uid.clone(),
+            associated_uid: association.associated_uid.clone(),
+            from: association.from,
+            location: association.location.clone(),
+            stp_indicator: association.stp_indicator,
+        }
+    }
+}
+
+impl fmt::Display for AssociationKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} and {} at {} from {} ({:?})",
+            self.base_uid, self.associated_uid, self.location, self.from, self.stp_indicator
+        )
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum UpdateError {
+    #[error("a store can only be created from a full extract")]
+    NotFullExtract,
+    #[error("only update extracts can be applied to a store")]
+    NotUpdateExtract,
+    #[error("schedule {0} already exists")]
+    DuplicateSchedule(ScheduleKey),
+    #[error("schedule {0} does not exist")]
+    ScheduleNotFound(ScheduleKey),
+    #[error("association {0} already exists")]
+    DuplicateAssociation(AssociationKey),
+    #[error("association {0} does not exist")]
+    AssociationNotFound(AssociationKey),
+    #[error("TIPLOC {0} already exists")]
+    DuplicateTiploc(Tiploc),
+    #[error("TIPLOC {0} does not exist")]
+    TiplocNotFound(Tiploc),
+    #[error(transparent)]
+    Sequence(#[from] SequenceError),
+    #[error(transparent)]
+    Read(#[from] Error),
+}
+
+/// Position of each record in a list, by its key
+#[derive(Debug, Clone)]
+struct Index<K>(HashMap<K, usize>);
+
+impl<K: Eq + Hash + Clone> Index<K> {
+    fn get(&self, key: &K) -> Option<usize> {
+        self.0.get(key).copied()
+    }
+
+    fn insert<V>(&mut self, items: &mut Vec<V>, key: K, item: V) {
+        self.0.insert(key, items.len());
+        items.push(item);
+    }
+
+    /// Removes an item, moving the last item into its place
+    fn remove<V>(&mut self, items: &mut Vec<V>, key: &K, key_of: impl Fn(&V) -> K) -> Option<V> {
+        let i = self.0.remove(key)?;
+        let item = items.swap_remove(i);
+
+        if let Some(moved) = items.get(i) {
+            self.0.insert(key_of(moved), i);
+        }
+
+        Some(item)
+    }
+}
+
+/// Keys of an index as they would be after the entries checked so far
+struct Pending<'a, K> {
+    index: &'a Index<K>,
+    /// Whether each key changed so far would then exist
+    changed: HashMap<K, bool>,
+}
+
+impl<'a, K: Eq + Hash + Clone> Pending<'a, K> {
+    fn new(index: &'a Index<K>) -> Self {
+        Pending {
+            index,
+            changed: HashMap::new(),
+        }
+    }
+
+    fn contains(&self, key: &K) -> bool {
+        match self.changed.get(key) {
+            Some(exists) => *exists,
+            None => self.index.get(key).is_some(),
+        }
+    }
+
+    /// Adds a key, returning whether it was new
+    fn insert(&mut self, key: &K) -> bool {
+        if self.contains(key) {
+            return false;
+        }
+
+        self.changed.insert(key.clone(), true);
+
+        true
+    }
+
+    /// Removes a key, returning whether it existed
+    fn remove(&mut self, key: &K) -> bool {
+        if !self.contains(key) {
+            return false;
+        }
+
+        self.changed.insert(key.clone(), false);
+
+        true
+    }
+
+    /// Checks a transaction on the record with a key, returning whether it
+    /// can be applied
+    fn transact(&mut self, key: &K, transaction_type: TransactionType) -> bool {
+        match transaction_type {
+            TransactionType::New => self.insert(key),
+            TransactionType::Revise => self.contains(key),
+            TransactionType::Delete => self.remove(key),
+        }
+    }
+}
+
+/// A timetable kept up to date by applying update extracts
+///
+/// The store is created from a full extract, then each daily update extract
+/// is applied in turn. Records are applied in the order they appear in the
+/// extract, according to their [TransactionType], with schedules matched on
+/// their [ScheduleKey] and associations on their [AssociationKey].
+///
+/// Updates must be applied in the order they were produced, as checked by
+/// [UpdateSequence]. Every record of an update is checked against the store
+/// before any is applied, so an update which fails leaves the store as it was
+/// and can be retried.
+#[derive(Debug, Clone)]
+pub struct TimetableStore {
+    timetable: Timetable,
+    tiplocs: Index<Tiploc>,
+    associations: Index<AssociationKey>,
+    schedules: Index<ScheduleKey>,
+    sequence: UpdateSequence,
+}
+
+impl TimetableStore {
+    /// Creates a store from a full extract
+    pub fn new(full: Timetable) -> Result<Self, UpdateError> {
+        if !matches!(full.header.extract_type, ExtractType::Full) {
+            return Err(UpdateError::NotFullExtract);
+        }
+
+        let mut store = TimetableStore {
+            timetable: Timetable {
+                header: full.header.clone(),
+                tiploc_inserts: Vec::new(),
+                tiploc_amends: Vec::new(),
+                tiploc_deletes: Vec::new(),
+                associations: Vec::new(),
+                train_schedules: Vec::new(),
+            },
+            tiplocs: Index(HashMap::new()),
+            associations: Index(HashMap::new()),
+            schedules: Index(HashMap::new()),
+            sequence: UpdateSequence::new(),
+        };
+
+        let tiplocs = full.tiploc_inserts.into_iter().map(Entry::TiplocInsert);
+        let amends = full.tiploc_amends.into_iter().map(Entry::TiplocAmend);
+        let deletes = full.tiploc_deletes.into_iter().map(Entry::TiplocDelete);
+        let associations = full.associations.into_iter().map(Entry::Association);
+        let schedules = full.train_schedules.into_iter().map(Entry::schedule);
+
+        let entries: Vec<Entry> = tiplocs
+            .chain(amends)
+            .chain(deletes)
+            .chain(associations)
+            .chain(schedules)
+            .collect();
+
+        store.check_entries(&entries)?;
+        store.sequence.advance(&full.header)?;
+
+        for entry in entries {
+            store.apply_entry(entry);
+        }
+
+        Ok(store)
+    }
+
+    /// The timetable as of the most recently applied extract
+    ///
+    /// TIPLOC amendments and deletions are applied to the inserts, so only
+    /// [Timetable::tiploc_inserts] is populated.
+    pub fn timetable(&self) -> &Timetable {
+        &self.timetable
+    }
+
+    pub fn into_timetable(self) -> Timetable {
+        self.timetable
+    }
+
+    /// Applies an update extract, read from its records
+    ///
+    /// The records are read up to the trailer, as
+    /// [Timetable::from_records] does, and an extract without one is
+    /// reported as [Error::Incomplete].
+    pub fn apply<I>(&mut self, records: I) -> Result<(), UpdateError>
+    where
+        I: IntoIterator<Item = crate::error::Result<Record>>,
+    {
+        let mut entries = Entries::new(records.into_iter());
+
+        let header = match entries.next().transpose()? {
+            Some(Entry::Header(header)) => header,
+            _ => return Err(Error::missing_field("header").into()),
+        };
+
+        if !matches!(header.extract_type, ExtractType::Update) {
+            return Err(UpdateError::NotUpdateExtract);
+        }
+
+        self.sequence.check(&header)?;
+
+        let mut update = Vec::new();
+
+        loop {
+            match entries.next().transpose()? {
+                Some(Entry::Trailer(_)) => break,
+                // Entries only returns a header first
+                Some(Entry::Header(_)) => unreachable!(),
+                Some(entry) => update.push(entry),
+                None => return Err(Error::Incomplete.into()),
+            }
+        }
+
+        self.check_entries(&update)?;
+        self.sequence.advance(&header)?;
+        self.timetable.header = header;
+
+        for entry in update {
+            self.apply_entry(entry);
+        }
+
+        Ok(())
+    }
+
+    /// Checks that each entry can be applied after the ones before it
+    fn check_entries(&self, entries: &[Entry]) -> Result<(), UpdateError> {
+        let mut tiplocs = Pending::new(&self.tiplocs);
+        let mut associations = Pending::new(&self.associations);
+        let mut schedules = Pending::new(&self.schedules);
+
+        for entry in entries {
+            match entry {
+                Entry::TiplocInsert(insert) => {
+                    if !tiplocs.insert(&insert.code) {
+                        return Err(UpdateError::DuplicateTiploc(insert.code.clone()));
+                    }
+                }
+                Entry::TiplocAmend(amend) => {
+                    let code = &amend.details.code;
+
+                    if !tiplocs.remove(code) {
+                        return Err(UpdateError::TiplocNotFound(code.clone()));
+                    }
+
+                    let new_code = amend.new_code.as_ref().unwrap_or(code);
+
+                    if !tiplocs.insert(new_code) {
+                        return Err(UpdateError::DuplicateTiploc(new_code.clone()));
+                    }
+                }
+                Entry::TiplocDelete(delete) => {
+                    if !tiplocs.remove(&delete.code) {
+                        return Err(UpdateError::TiplocNotFound(delete.code.clone()));
+                    }
+                }
+                Entry::Association(association) => {
+                    let key = AssociationKey::new(association);
+                    let transaction_type = association.transaction_type;
+
+                    if !associations.transact(&key, transaction_type) {
+                        return Err(match transaction_type {
+                            TransactionType::New => UpdateError::DuplicateAssociation(key),
+                            _ => UpdateError::AssociationNotFound(key),
+                        });
+                    }
+                }
+                Entry::Schedule(schedule) => {
+                    let key = ScheduleKey::new(schedule);
+                    let transaction_type = schedule.bs.transaction_type;
+
+                    if !schedules.transact(&key, transaction_type) {
+                        return Err(match transaction_type {
+                            TransactionType::New => UpdateError::DuplicateSchedule(key),
+                            _ => UpdateError::ScheduleNotFound(key),
+                        });
+                    }
+                }
+                Entry::Header(_) | Entry::Trailer(_) => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies an entry which has been checked by [Self::check_entries]
+    fn apply_entry(&mut self, entry: Entry) {
+        let tiploc_of = |tiploc: &TiplocInsert| tiploc.code.clone();
+
+        match entry {
+            Entry::TiplocInsert(insert) => self.tiplocs.insert(
+                &mut self.timetable.tiploc_inserts,
+                insert.code.clone(),
+                insert,
+            ),
+            Entry::TiplocAmend(amend) => {
+                let code = &amend.details.code;
+
+                self.tiplocs
+                    .remove(&mut self.timetable.tiploc_inserts, code, tiploc_of);
+
+                let mut details = amend.details;
+
+                if let Some(new_code) = amend.new_code {
+                    details.code = new_code;
+                }
+
+                self.tiplocs.insert(
+                    &mut self.timetable.tiploc_inserts,
+                    details.code.clone(),
+                    details,
+                );
+            }
+            Entry::TiplocDelete(delete) => {
+                self.tiplocs
+                    .remove(&mut self.timetable.tiploc_inserts, &delete.code, tiploc_of);
+            }
+            Entry::Association(association) => {
+                let key = AssociationKey::new(&association);
+                let associations = &mut self.timetable.associations;
+
+                match association.transaction_type {
+                    TransactionType::New => {
+                        self.associations.insert(associations, key, association)
+                    }
+                    TransactionType::Revise => {
+                        if let Some(i) = self.associations.get(&key) {
+                            associations[i] = association;
+                        }
+                    }
+                    TransactionType::Delete => {
+                        self.associations
+                            .remove(associations, &key, AssociationKey::new);
+                    }
+                }
+            }
+            Entry::Schedule(schedule) => {
+                let key = ScheduleKey::new(&schedule);
+                let schedules = &mut self.timetable.train_schedules;
+
+                match schedule.bs.transaction_type {
+                    TransactionType::New => self.schedules.insert(schedules, key, *schedule),
+                    TransactionType::Revise => {
+                        if let Some(i) = self.schedules.get(&key) {
+                            schedules[i] = *schedule;
+                        }
+                    }
+                    TransactionType::Delete => {
+                        self.schedules.remove(schedules, &key, ScheduleKey::new);
+                    }
+                }
+            }
+            Entry::Header(_) | Entry::Trailer(_) => {}
+        }
+    }
+}
+
+#[test]
+fn apply_updates() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::reader::RecordReader;
+
+    let full = r#"HDTPS.UDFROC1.PD2502282802252154DFROC1B       FA280225280226                    
+TIAACHEN 00081601LAACHEN                    00005   0                           
+BSNC123452505182506011111100 POO2N75    113575825 EMU    090      S            P
+BX         TPY                                                                  
+LOMNCRIAP 1610 16104A        TB                                                 
+LTEDINBUR 1939 193912 X  TF                                                     
+BSNC234562505182506011111100 POO2N75    113575825 EMU    090      S            P
+BX         TPY                                                                  
+LOMNCRIAP 1610 16104A        TB                                                 
+LTEDINBUR 1939 193912 X  TF                                                     
+ZZ                                                                              "#;
+    let update = r#"HDTPS.UDFROC1.PD2503012802252154DFROC1CDFROC1BUA010325010326                    
+TDAACHEN                                                                        
+BSRC123452505182506011111100 POO2N75    113575825 EMU    100      S            P
+BX         TPY                                                                  
+LOMNCRIAP 1610 16104A        TB                                                 
+LTEDINBUR 1939 193912 X  TF                                                     
+BSDC23456250518                                                                P
+BSNC345672505192505191000000 POO2N75    113575825 EMU    090      S            O
+BX         TPY                                                                  
+LOMNCRIAP 1610 16104A        TB                                                 
+LTEDINBUR 1939 193912 X  TF                                                     
+ZZ                                                                              "#;
+    let unmatched = r#"HDTPS.UDFROC1.PD2503022802252154DFROC1DDFROC1CUA020325020326                    
+BSNC456782505192505191000000 POO2N75    113575825 EMU    090      S            O
+BX         TPY                                                                  
+LOMNCRIAP 1610 16104A        TB                                                 
+LTEDINBUR 1939 193912 X  TF                                                     
+BSDC99999250518                                                                P
+ZZ                                                                              "#;
+    let corrected = r#"HDTPS.UDFROC1.PD2503022802252154DFROC1DDFROC1CUA020325020326                    
+TIAACHEN 00081601LAACHEN                    00005   0                           
+TDAACHEN                                                                        
+TIAACHEN 00081601LAACHEN                    00005   0                           
+BSNC456782505192505191000000 POO2N75    113575825 EMU    090      S            O
+BX         TPY                                                                  
+LOMNCRIAP 1610 16104A        TB                                                 
+LTEDINBUR 1939 193912 X  TF                                                     
+ZZ                                                                              "#;
+    let duplicate = r#"HDTPS.UDFROC1.PD2503032802252154DFROC1EDFROC1DUA030325030326                    
+TIAACHEN 00081601LAACHEN                    00005   0                           
+ZZ                                                                              "#;
+
+    let mut store = TimetableStore::new(full.parse()?)?;
+
+    assert_eq!(store.timetable().tiploc_inserts.len(), 1);
+    assert_eq!(store.timetable().train_schedules.len(), 2);
+    assert!(matches!(
+        store.apply(RecordReader::new(full.as_bytes())),
+        Err(UpdateError::NotUpdateExtract)
+    ));
+
+    store.apply(RecordReader::new(update.as_bytes()))?;
+
+    assert!(matches!(
+        store.apply(RecordReader::new(update.as_bytes())),
+        Err(UpdateError::Sequence(SequenceError::Duplicate(_)))
+    ));
+
+    let timetable = store.timetable();
+    let uids: Vec<&str> = timetable
+        .train_schedules
+        .iter()
+        .map(|schedule| schedule.bs.train_uid.as_str())
+        .collect();
+
+    assert!(timetable.tiploc_inserts.is_empty());
+    assert_eq!(uids, vec!["C12345", "C34567"]);
+    assert_eq!(timetable.train_schedules[0].bs.speed, Some(100));
+
+    assert!(matches!(
+        store.apply(RecordReader::new(unmatched.as_bytes())),
+        Err(UpdateError::ScheduleNotFound(key)) if key.train_uid == "C99999"
+    ));
+
+    // A failed update leaves the store as it was, so it can be retried
+    assert_eq!(store.timetable().train_schedules.len(), 2);
+    assert_eq!(store.timetable().header.current_file_ref, "DFROC1C");
+    assert_eq!(store.sequence.last_file_ref(), Some("DFROC1C"));
+
+    store.apply(RecordReader::new(corrected.as_bytes()))?;
+
+    assert_eq!(store.timetable().train_schedules.len(), 3);
+    assert_eq!(store.sequence.last_file_ref(), Some("DFROC1D"));
+
+    // Records are applied in the order they appear, so a TIPLOC can be
+    // deleted and inserted again, but not inserted twice
+    assert_eq!(store.timetable().tiploc_inserts.len(), 1);
+    assert!(matches!(
+        store.apply(RecordReader::new(duplicate.as_bytes())),
+        Err(UpdateError::DuplicateTiploc(code)) if &*code == "AACHEN"
+    ));
+
+    assert_eq!(store.timetable().tiploc_inserts.len(), 1);
+    assert_eq!(store.sequence.last_file_ref(), Some("DFROC1D"));
+
+    Ok(())
+}
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+
+use crate::extract::{HolidayCalendar, StpIndicator, TransactionType};
+
+use super::{Timetable, TrainSchedule};
+
+/// Why a schedule which applies on a date is not the one the train runs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SupersededReason {
+    /// An STP cancellation applies on the date
+    Cancelled,
+    /// An STP overlay or new schedule applies on the date
+    Overlaid,
+    /// Another schedule with the same STP indicator applies on the date, and
+    /// appears later in the timetable
+    Duplicate,
+}
+
+/// A schedule which applies on a date, but has been superseded by another
+/// schedule for the same train
+#[derive(Debug)]
+pub struct Superseded<'a> {
+    pub schedule: &'a TrainSchedule,
+    /// The schedule taking precedence
+    pub by: &'a TrainSchedule,
+    pub reason: SupersededReason,
+}
+
+/// The schedules of a timetable for a single date, once STP precedence has
+/// been applied
+#[derive(Debug, Default)]
+pub struct EffectiveSchedules<'a> {
+    /// Schedules trains run to on the date, at most one for each train UID
+    pub running: Vec<&'a TrainSchedule>,
+    /// Schedules which apply on the date but are not run to
+    ///
+    /// Cancellations themselves are never listed, only the schedules they
+    /// cancel.
+    pub superseded: Vec<Superseded<'a>>,
+}
+
+impl Timetable {
+    /// Resolves the schedules valid on a date
+    ///
+    /// Each train UID is resolved on its own. Of the schedules which run on the
+    /// date, the one whose STP indicator has the highest
+    /// [precedence](StpIndicator::precedence) wins, and a cancellation winning
+    /// means the train doesn't run at all. Deletions are ignored, as they
+    /// aren't schedules in their own right.
+    pub fn effective_schedules(
+        &self,
+        date: NaiveDate,
+        calendar: &impl HolidayCalendar,
+    ) -> EffectiveSchedules<'_> {
+        let mut trains: Vec<Vec<&TrainSchedule>> = Vec::new();
+        let mut index: HashMap<&str, usize> = HashMap::new();
+
+        let applicable = self.train_schedules.iter().filter(|schedule| {
+            schedule.bs.transaction_type != TransactionType::Delete
+                && schedule.bs.runs_on(date, calendar)
+        });
+
+        for schedule in applicable {
+            let i = *index.entry(&schedule.bs.train_uid).or_insert_with(|| {
+                trains.push(Vec::new());
+                trains.len() - 1
+            });
+
+            trains[i].push(schedule);
+        }
+
+        let mut effective = EffectiveSchedules::default();
+
+        for schedules in trains {
+            // The last of equal precedence wins, as it was published later
+            let Some(winner) = schedules
+                .iter()
+                .copied()
+                .max_by_key(|schedule| schedule.bs.stp_indicator.precedence())
+            else {
+                continue;
+            };
+
+            if winner.bs.stp_indicator != StpIndicator::Cancellation {
+                effective.running.push(winner);
+            }
+
+            for schedule in schedules {
+                if std::ptr::eq(schedule, winner)
+                    || schedule.bs.stp_indicator == StpIndicator::Cancellation
+                {
+                    continue;
+                }
+
+                let reason = if winner.bs.stp_indicator == StpIndicator::Cancellation {
+                    SupersededReason::Cancelled
+                } else if schedule.bs.stp_indicator.precedence()
+                    < winner.bs.stp_indicator.precedence()
+                {
+                    SupersededReason::Overlaid
+                } else {
+                    SupersededReason::Duplicate
+                };
+
+                effective.superseded.push(Superseded {
+                    schedule,
+                    by: winner,
+                    reason,
+                });
+            }
+        }
+
+        effective
+    }
+
+    /// Schedules trains run to on a date, after STP precedence has been
+    /// applied
+    ///
+    /// See [Timetable::effective_schedules] for how the schedules are chosen.
+    pub fn schedules_on(
+        &self,
+        date: NaiveDate,
+        calendar: &impl HolidayCalendar,
+    ) -> Vec<&TrainSchedule> {
+        self.effective_schedules(date, calendar).running
+    }
+}
+
+#[test]
+fn effective_schedules() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::extract::NoHolidays;
+
+    let raw = r#"HDTPS.UDFROC1.PD2502282802252154DFROC1B       FA280225280226                    
+BSNC123452505182506011111100 POO2N75    113575825 EMU    090      S            P
+BX         TPY                                                                  
+LOMNCRIAP 1610 16104A        TB                                                 
+LTEDINBUR 1939 193912 X  TF                                                     
+BSNC123452505192505191000000 POO2N75    113575825 EMU    090      S            O
+BX         TPY                                                                  
+LOMNCRIAP 1615 16154A        TB                                                 
+LTEDINBUR 1939 193912 X  TF                                                     
+BSNC123452505202505200100000                                                   C
+ZZ                                                                              "#;
+
+    let timetable: Timetable = raw.parse()?;
+    let date = |day| NaiveDate::from_ymd_opt(2025, 5, day).unwrap();
+
+    let overlaid = timetable.effective_schedules(date(19), &NoHolidays);
+    assert_eq!(overlaid.running.len(), 1);
+    assert_eq!(overlaid.running[0].bs.stp_indicator, StpIndicator::Overlay);
+    assert_eq!(overlaid.superseded.len(), 1);
+    assert_eq!(overlaid.superseded[0].reason, SupersededReason::Overlaid);
+    assert_eq!(
+        overlaid.superseded[0].schedule.bs.stp_indicator,
+        StpIndicator::Permanent
+    );
+
+    let cancelled = timetable.effective_schedules(date(20), &NoHolidays);
+    assert!(cancelled.running.is_empty());
+    assert_eq!(cancelled.superseded.len(), 1);
+    assert_eq!(cancelled.superseded[0].reason, SupersededReason::Cancelled);
+
+    let permanent = timetable.schedules_on(date(21), &NoHolidays);
+    assert_eq!(permanent.len(), 1);
+    assert_eq!(permanent[0].bs.stp_indicator, StpIndicator::Permanent);
+
+    assert!(timetable.schedules_on(date(24), &NoHolidays).is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,262 @@
+//! Writing records and timetables back out as CIF
+//!
+//! Each record type implements [fmt::Display], writing itself as a single
+//! fixed width line. [Serializer] writes whole extracts, one line at a time,
+//! for anything implementing [ToCif].
+
+use std::{fmt, io};
+
+use crate::{
+    error::Result,
+    extract::{
+        Association, BasicSchedule, BasicScheduleExtra, ChangeEnRoute, Header,
+        IntermediateLocation, OriginLocation, Record, TerminatingLocation, TiplocAmend,
+        TiplocDelete, TiplocInsert, Trailer,
+    },
+    timetable::{Timetable, TrainSchedule},
+};
+
+/// Writes records to a writer as lines of CIF
+///
+/// Lines are separated by `\n`, without one after the last line, so an
+/// extract read with [Timetable::from_str](std::str::FromStr::from_str) is
+/// written back unchanged.
+pub struct Serializer<W> {
+    writer: W,
+    first: bool,
+}
+
+impl<W: io::Write> Serializer<W> {
+    pub fn new(writer: W) -> Self {
+        Serializer {
+            writer,
+            first: true,
+        }
+    }
+
+    /// Writes a single record as a line
+    pub fn serialize_record(&mut self, record: &impl fmt::Display) -> Result<()> {
+        if !self.first {
+            self.writer.write_all(b"\n")?;
+        }
+
+        self.first = false;
+
+        write!(self.writer, "{record}")?;
+
+        Ok(())
+    }
+
+    /// Writes every record making up a value
+    pub fn serialize<T: ToCif + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.to_cif(self)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Types which can be written as one or more records of CIF
+pub trait ToCif {
+    fn to_cif<W: io::Write>(&self, serializer: &mut Serializer<W>) -> Result<()>;
+}
+
+macro_rules! single_record {
+    ($($record:ty),* $(,)?) => {
+        $(
+            impl ToCif for $record {
+                fn to_cif<W: io::Write>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+                    serializer.serialize_record(self)
+                }
+            }
+        )*
+    };
+}
+
+single_record!(
+    Record,
+    Header,
+    TiplocInsert,
+    TiplocAmend,
+    TiplocDelete,
+    Association,
+    BasicSchedule,
+    BasicScheduleExtra,
+    OriginLocation,
+    IntermediateLocation,
+    ChangeEnRoute,
+    TerminatingLocation,
+    Trailer,
+);
+
+impl<T: ToCif> ToCif for [T] {
+    fn to_cif<W: io::Write>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+        self.iter().try_for_each(|value| value.to_cif(serializer))
+    }
+}
+
+impl<T: ToCif> ToCif for Vec<T> {
+    fn to_cif<W: io::Write>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+        self.as_slice().to_cif(serializer)
+    }
+}
+
+impl ToCif for TrainSchedule {
+    /// Writes the schedule in calling pattern order, with each change en route
+    /// directly before the intermediate location it applies to, and each
+    /// location note directly after the location it is given for
+    fn to_cif<W: io::Write>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+        serializer.serialize_record(&self.bs)?;
+
+        if let Some(bsx) = &self.bsx {
+            serializer.serialize_record(bsx)?;
+        }
+
+        for note in &self.train_notes {
+            serializer.serialize_record(&format_args!("TN{note}"))?;
+        }
+
+        let mut changes = self.changes_en_route.iter().peekable();
+        let mut notes = self.location_notes.iter().peekable();
+        let mut write_notes = |serializer: &mut Serializer<W>, index| {
+            while let Some(ln) = notes.next_if(|ln| ln.location_index <= index) {
+                serializer.serialize_record(&format_args!("LN{}", ln.note))?;
+            }
+
+            Ok::<_, crate::error::Error>(())
+        };
+
+        if let Some(origin) = &self.origin_location {
+            serializer.serialize_record(origin)?;
+        }
+
+        write_notes(serializer, 0)?;
+
+        // Intermediate locations start at index 1 of the calling pattern
+        for (index, location) in (1..).zip(&self.intermediate_locations) {
+            while let Some(cr) = changes.next_if(|cr| cr.location_index <= index) {
+                serializer.serialize_record(&cr.change)?;
+            }
+
+            serializer.serialize_record(location)?;
+            write_notes(serializer, index)?;
+        }
+
+        // Changes after the last intermediate location are kept rather than
+        // dropped
+        for cr in changes {
+            serializer.serialize_record(&cr.change)?;
+        }
+
+        if let Some(terminating) = &self.terminating_location {
+            serializer.serialize_record(terminating)?;
+        }
+
+        write_notes(serializer, usize::MAX)
+    }
+}
+
+impl ToCif for Timetable {
+    fn to_cif<W: io::Write>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+        serializer.serialize(&self.header)?;
+        serializer.serialize(&self.tiploc_inserts)?;
+        serializer.serialize(&self.tiploc_amends)?;
+        serializer.serialize(&self.tiploc_deletes)?;
+        serializer.serialize(&self.associations)?;
+        serializer.serialize(&self.train_schedules)?;
+        serializer.serialize(&Trailer)
+    }
+}
+
+#[test]
+fn write_records() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let raw = r#"HDTPS.UDFROC1.PD2503012802252154DFROC1CDFROC1BUA010325010326                    
+TAAACHEN 00081601LAACHEN HBF                00005   0   AACHEN HBF      AACHENH 
+TDAACHEN                                                                        
+AADC12345C23456250518                 MNCRPIC                                  P
+BSDC23456250518                                                                P
+BSNP132082412152505110000001 PXX1S758044121730001 EMU397 125      B A F        P
+BX         VTYVT123400                                                          
+LOMNCRIAP 1610 16104A        TB                                                 
+LIHLDGWJ            1612 00000000                                               
+CRMNCRPIC XX1S758044121730001 EMU397 125      B A F       CL86     VT123400     
+LIMNCRPIC 1624H1626H     1625162614    SL T                                     
+LTEDINBUR 1939 193912 X  TF                                                     
+ZZ                                                                              "#;
+
+    let timetable: Timetable = raw.parse()?;
+
+    let changes = &timetable.train_schedules[1].changes_en_route;
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].change.traction_class.as_deref(), Some("CL86"));
+    assert_eq!(crate::to_string(&timetable), raw);
+
+    Ok(())
+}
+
+#[test]
+fn write_fields_as_read() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    // Fields which CIF allows to be laid out more than one way
+    let cases = [
+        // Half a minute on its own, in the first character
+        "LIHLDGWJ            1612 00000000                     H                         ",
+        // Minutes from the second character, or with a leading zero
+        "LIHLDGWJ            1612 00000000                        2                      ",
+        "LIHLDGWJ            1612 00000000                         01                    ",
+        // Activities with blanks between them
+        "LIHLDGWJ            1612 00000000         T   RM                                ",
+        // Blank public times
+        "LIHLDGWJ            1612                                                        ",
+        // A timing load with a leading zero
+        "BSNP132082412152505110000001 PXX1S758044121730001 D  0450125      B A F        P",
+        // A blank applicable timetable code
+        "BX         TP                                                                   ",
+    ];
+
+    for raw in cases {
+        assert_eq!(raw.parse::<Record>()?.to_string(), raw);
+    }
+
+    // A field which has been changed since is written in the usual layout
+    let mut location: IntermediateLocation = cases[1].parse()?;
+    location.pathing_allowance = Some("2H".parse()?);
+    assert_eq!(
+        location.to_string(),
+        "LIHLDGWJ            1612 00000000                       2H                      "
+    );
+
+    Ok(())
+}
+
+#[test]
+fn write_notes() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let raw = r#"HDTPS.UDFROC1.PD2503012802252154DFROC1CDFROC1BUA010325010326                    
+BSNP132082412152505110000001 PXX1S758044121730001 EMU397 125      B A F        P
+BX         TPY                                                                  
+TNA note for the whole train                                                    
+LOMNCRIAP 1610 16104A        TB                                                 
+LNA note for the origin                                                         
+LIHLDGWJ            1612 00000000                                               
+LNA note for this location                                                      
+CRMNCRPIC XX1S758044121730001 EMU397 125      B A F                VT123400     
+LIMNCRPIC 1624H1626H     1625162614    SL T                                     
+LTEDINBUR 1939 193912 X  TF                                                     
+LNA note for the terminating location                                           
+ZZ                                                                              "#;
+
+    let timetable: Timetable = raw.parse()?;
+
+    let schedule = &timetable.train_schedules[0];
+    assert_eq!(schedule.train_notes.len(), 1);
+    let indices: Vec<_> = schedule
+        .location_notes
+        .iter()
+        .map(|ln| ln.location_index)
+        .collect();
+    assert_eq!(indices, [0, 1, 3]);
+
+    assert_eq!(crate::to_string(&timetable), raw);
+
+    Ok(())
+}
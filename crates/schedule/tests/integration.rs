@@ -1,31 +1,399 @@
-use std::fs;
+use std::{fs, io::BufReader};
 
-use cify::{
-    extract::{Header, Record},
-    timetable::Timetable,
-};
+use cify::{error::Error, extract::Record, reader::RecordReader, timetable::Timetable};
 
+#[cfg(feature = "serde")]
 #[test]
 fn deserialize_array() -> Result<(), Box<dyn std::error::Error>> {
     let raw = fs::read_to_string("tests/extract.cif")?;
 
     let result = cify::from_str::<Vec<Record>>(&raw)?;
 
+    assert_eq!(result.len(), raw.lines().count());
+
     Ok(())
 }
 
+#[cfg(feature = "serde")]
 #[test]
 fn deserialize_extract() -> Result<(), Box<dyn std::error::Error>> {
     let raw = fs::read_to_string("tests/extract.cif")?;
 
     let result = cify::from_str::<Timetable>(&raw)?;
 
+    assert_eq!(result.associations.len(), 1);
+
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn deserialize_truncated_extract() -> Result<(), Box<dyn std::error::Error>> {
+    let raw = fs::read_to_string("tests/extract.cif")?;
+    let truncated = &raw[..raw.len() / 2];
+
+    let result = cify::from_str::<Timetable>(truncated);
+
+    assert!(matches!(result, Err(Error::Incomplete)));
+
+    let without_trailer = raw.lines().take(61).collect::<Vec<_>>().join("\n");
+
+    assert!(matches!(
+        cify::from_str::<Timetable>(&without_trailer),
+        Err(Error::Incomplete)
+    ));
+
+    // An error in the last schedule isn't put down to a missing trailer when
+    // the trailer is there
+    let without_origin = raw.replace(raw.lines().nth(15).ok_or("expected an LO")?, "");
+    let without_origin = without_origin.replace("\n\n", "\n");
+
+    assert!(matches!(
+        cify::from_str::<Timetable>(&without_origin),
+        Err(Error::Syntax(_))
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn deserialize_malformed_extract() -> Result<(), Box<dyn std::error::Error>> {
+    let raw = fs::read_to_string("tests/extract.cif")?
+        .replace("LIHLDGWJ            1612 ", "LIHLDGWJ            16X2 ");
+
+    let strict = cify::from_str::<Timetable>(&raw);
+
+    assert!(matches!(
+        strict,
+        Err(Error::Record { line: 17, ref identity, .. }) if identity == "LI"
+    ));
+
+    let (timetable, diagnostics) = cify::from_str_lenient::<Timetable>(&raw)?;
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].line, 17);
+    assert_eq!(diagnostics[0].identity, "LI");
+    assert_eq!(
+        timetable.train_schedules[0].intermediate_locations.len(),
+        43
+    );
+
+    let (records, diagnostics) = cify::from_str_lenient::<Vec<Record>>(&raw)?;
+
+    assert_eq!(records.len(), raw.lines().count() - 1);
+    assert_eq!(diagnostics.len(), 1);
+
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn deserialize_many_cancellations() -> Result<(), Box<dyn std::error::Error>> {
+    use cify::timetable::TrainSchedule;
+
+    // A cancellation is a lone BS record, so each schedule only ends when the
+    // next one starts
+    let cancellations = (0..20_000)
+        .map(|i| format!("BSNC{i:05}2505202505200100000{:51}C", ""))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let raw = fs::read_to_string("tests/extract.cif")?;
+    let header = raw.lines().next().ok_or("expected a header")?;
+    let trailer = raw.lines().last().ok_or("expected a trailer")?;
+    let extract = format!("{header}\n{cancellations}\n{trailer}");
+
+    let timetable = cify::from_str::<Timetable>(&extract)?;
+    assert_eq!(timetable.train_schedules.len(), 20_000);
+
+    let schedules = cify::from_str::<Vec<TrainSchedule>>(&cancellations)?;
+    assert_eq!(schedules.len(), 20_000);
+
+    let malformed = format!("{cancellations}\nBSNC999992505202505320100000{:51}C", "");
+
+    assert!(matches!(
+        cify::from_str::<Vec<TrainSchedule>>(&malformed),
+        Err(Error::Record { line: 20_001, .. })
+    ));
+
+    Ok(())
+}
+
+/// Replaces a line of an extract, numbered from 1
+fn replace_line(raw: &str, number: usize, replace: impl Fn(&str) -> String) -> String {
+    raw.lines()
+        .enumerate()
+        .map(|(i, line)| match i + 1 == number {
+            true => replace(line),
+            false => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn lenient_group_boundaries() -> Result<(), Box<dyn std::error::Error>> {
+    use cify::{error::Diagnostic, Mode};
+
+    let raw = fs::read_to_string("tests/extract.cif")?;
+
+    // The last TIPLOC cut short, then an association and a schedule with an
+    // unknown transaction type, each the first or last line of its group, and
+    // a malformed origin which leaves its schedule unbuildable
+    let cases = [
+        (
+            12,
+            "TI",
+            replace_line(&raw, 12, |line| line[..40].to_string()),
+            10,
+            1,
+            1,
+        ),
+        (
+            13,
+            "AA",
+            replace_line(&raw, 13, |line| line.replacen("AAN", "AAX", 1)),
+            11,
+            0,
+            1,
+        ),
+        (
+            14,
+            "BS",
+            replace_line(&raw, 14, |line| line.replacen("BSN", "BSX", 1)),
+            11,
+            1,
+            0,
+        ),
+        (
+            16,
+            "LO",
+            replace_line(&raw, 16, |line| line.replacen("1610 ", "16X0 ", 1)),
+            11,
+            1,
+            0,
+        ),
+    ];
+
+    for (number, identity, raw, tiplocs, associations, schedules) in cases {
+        let check = |timetable: &Timetable, diagnostics: &[Diagnostic]| {
+            assert_eq!(diagnostics.len(), 1, "line {number}");
+            assert_eq!(diagnostics[0].line, number);
+            assert_eq!(diagnostics[0].identity, identity);
+            assert_eq!(timetable.tiploc_inserts.len(), tiplocs, "line {number}");
+            assert_eq!(timetable.associations.len(), associations, "line {number}");
+            assert_eq!(timetable.train_schedules.len(), schedules, "line {number}");
+        };
+
+        let (timetable, diagnostics) =
+            Timetable::from_records_lenient(RecordReader::new(raw.as_bytes()))?;
+        check(&timetable, &diagnostics);
+
+        let mut records = RecordReader::with_mode(raw.as_bytes(), Mode::Lenient);
+        let timetable = Timetable::from_records(&mut records)?;
+        check(&timetable, records.diagnostics());
+
+        #[cfg(feature = "serde")]
+        {
+            let (timetable, diagnostics) = cify::from_str_lenient::<Timetable>(&raw)?;
+            check(&timetable, &diagnostics);
+
+            let (_, diagnostics) = cify::from_str_lenient::<Vec<Record>>(&raw)?;
+            assert_eq!(diagnostics.len(), 1);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn deserialize_lenient_schedules() -> Result<(), Box<dyn std::error::Error>> {
+    use cify::timetable::TrainSchedule;
+
+    let raw = fs::read_to_string("tests/extract.cif")?;
+    let schedule = raw.lines().skip(13).take(48).collect::<Vec<_>>().join("\n");
+
+    // The malformed cancellation is the first line after the one before it
+    let cancellation = format!("BSNC000012505202505200100000{:51}C", "");
+    let malformed = format!("BSNC000022505202505320100000{:51}C", "");
+    let input = format!("{cancellation}\n{malformed}\n{schedule}\n{malformed}");
+
+    let (schedules, diagnostics) = cify::from_str_lenient::<Vec<TrainSchedule>>(&input)?;
+
+    assert_eq!(schedules.len(), 2);
+    assert_eq!(schedules[1].intermediate_locations.len(), 44);
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0].line, 2);
+    assert_eq!(diagnostics[1].line, 51);
+
+    // A schedule with a malformed origin is skipped as a whole
+    let malformed = schedule.replacen("1610 ", "16X0 ", 1);
+    let input = format!("{cancellation}\n{malformed}\n{schedule}");
+
+    let (schedules, diagnostics) = cify::from_str_lenient::<Vec<TrainSchedule>>(&input)?;
+
+    assert_eq!(schedules.len(), 2);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].line, 4);
+    assert_eq!(diagnostics[0].identity, "LO");
+
     Ok(())
 }
 
+#[test]
+fn parse_extract() -> Result<(), Box<dyn std::error::Error>> {
+    let raw = fs::read_to_string("tests/extract.cif")?;
+
+    let timetable = raw.parse::<Timetable>()?;
+
+    assert_eq!(timetable.associations.len(), 1);
+    assert_eq!(timetable.train_schedules.len(), 1);
+
+    let file = BufReader::new(fs::File::open("tests/extract.cif")?);
+    let timetable = Timetable::from_reader(file)?;
+
+    assert_eq!(
+        timetable.train_schedules[0].intermediate_locations.len(),
+        44
+    );
+
+    let without_trailer = raw.lines().take(20).collect::<Vec<_>>().join("\n");
+
+    assert!(matches!(
+        without_trailer.parse::<Timetable>(),
+        Err(Error::Incomplete)
+    ));
+
+    let without_origin = raw.replace(raw.lines().nth(15).ok_or("expected an LO")?, "");
+    let without_origin = without_origin.replace("\n\n", "\n");
+
+    assert!(matches!(
+        without_origin.parse::<Timetable>(),
+        Err(Error::Syntax(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    let raw = fs::read_to_string("tests/extract.cif")?;
+
+    let records = RecordReader::new(raw.as_bytes()).collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(cify::to_string(&records), raw);
+
+    let timetable = raw.parse::<Timetable>()?;
+    assert_eq!(cify::to_string(&timetable), raw);
+
+    let mut written = Vec::new();
+    cify::to_writer(&mut written, &timetable)?;
+    assert_eq!(written, raw.as_bytes());
+
+    #[cfg(feature = "serde")]
+    {
+        let records = cify::from_str::<Vec<Record>>(&raw)?;
+        assert_eq!(cify::to_string(&records), raw);
+
+        let timetable = cify::from_str::<Timetable>(&raw)?;
+        assert_eq!(cify::to_string(&timetable), raw);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn records_out_of_order() -> Result<(), Box<dyn std::error::Error>> {
+    let raw = fs::read_to_string("tests/extract.cif")?;
+    let mut lines: Vec<_> = raw.lines().collect();
+
+    // The association follows the schedule instead of preceding it
+    let association = lines.remove(12);
+    lines.insert(lines.len() - 1, association);
+    let moved = lines.join("\n");
+
+    let timetable = moved.parse::<Timetable>()?;
+    assert_eq!(timetable.associations.len(), 1);
+    assert_eq!(timetable.train_schedules.len(), 1);
+
+    #[cfg(feature = "serde")]
+    {
+        let timetable = cify::from_str::<Timetable>(&moved)?;
+        assert_eq!(timetable.associations.len(), 1);
+        assert_eq!(timetable.train_schedules.len(), 1);
+    }
+
+    // Only the first record may be a header
+    lines.insert(20, lines[0]);
+    let repeated = lines.join("\n");
+
+    assert!(matches!(
+        repeated.parse::<Timetable>(),
+        Err(Error::Syntax(_))
+    ));
+
+    #[cfg(feature = "serde")]
+    assert!(matches!(
+        cify::from_str::<Timetable>(&repeated),
+        Err(Error::Syntax(_))
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serialize_json() -> Result<(), Box<dyn std::error::Error>> {
+    use serde_json::json;
+
+    let raw = fs::read_to_string("tests/extract.cif")?;
+    let timetable = raw.parse::<Timetable>()?;
+
+    let json = serde_json::to_value(&timetable)?;
+
+    assert_eq!(json["header"]["extract_start_date"], "2025-02-28");
+    assert_eq!(json["header"]["datetime_of_extract"], "2025-02-28T21:54:00");
+    assert_eq!(json["header"]["current_file_ref"], "DFROC1B");
+    assert_eq!(json["header"]["last_file_ref"], json!(null));
+    assert_eq!(json["tiploc_inserts"][0]["code"], "AACHEN");
+    assert_eq!(json["tiploc_inserts"][0]["tps_description"], "AACHEN");
+    assert_eq!(json["tiploc_inserts"][0]["stanox"], "00005");
+    assert_eq!(json["tiploc_inserts"][0]["po_mcp_code"], json!(null));
+    assert_eq!(json["tiploc_inserts"][0]["crs"], json!(null));
+    assert_eq!(json["tiploc_inserts"][0]["description"], json!(null));
+    assert_eq!(
+        json["associations"][0]["days"],
+        json!(["Mon", "Tue", "Wed", "Thu", "Fri"])
+    );
+
+    let schedule = &json["train_schedules"][0];
+
+    assert_eq!(schedule["bs"]["from"], "2024-12-15");
+    assert_eq!(schedule["bs"]["power_type"], "EMU");
+    assert_eq!(schedule["bs"]["stp_indicator"], "P");
+    assert_eq!(
+        schedule["origin_location"]["scheduled_departure_time"],
+        json!({"hour": 16, "minute": 10, "second": 0})
+    );
+    assert_eq!(
+        schedule["intermediate_locations"][2]["scheduled_pass_time"],
+        json!({"hour": 16, "minute": 19, "second": 30})
+    );
+    assert_eq!(schedule["origin_location"]["activity"], json!(["TB"]));
+
+    let records = RecordReader::new(raw.as_bytes()).collect::<Result<Vec<_>, _>>()?;
+    let trailer = serde_json::to_value(records.last())?;
+
+    assert_eq!(trailer, json!({"ZZ": null}));
+
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
 #[test]
 fn deserialize_string() -> Result<(), Box<dyn std::error::Error>> {
-    let result: Header = cify::from_str(
+    let result: cify::extract::Header = cify::from_str(
         "TPS.UDFROC1.PD2502282802252154DFROC1B       FA280225280226                    ",
     )?;
 
@@ -33,3 +401,209 @@ fn deserialize_string() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[test]
+fn read_records() -> Result<(), Box<dyn std::error::Error>> {
+    let file = BufReader::new(fs::File::open("tests/extract.cif")?);
+    let raw = fs::read_to_string("tests/extract.cif")?;
+
+    let records = RecordReader::new(file).collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(records.len(), raw.lines().count());
+    assert!(matches!(records.last(), Some(Record::ZZ(_))));
+
+    Ok(())
+}
+
+#[test]
+fn read_schedules() -> Result<(), Box<dyn std::error::Error>> {
+    let file = BufReader::new(fs::File::open("tests/extract.cif")?);
+
+    let schedules = RecordReader::new(file)
+        .schedules()
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(schedules.len(), 1);
+    assert_eq!(schedules[0].intermediate_locations.len(), 44);
+
+    Ok(())
+}
+
+#[test]
+fn record_errors() -> Result<(), Box<dyn std::error::Error>> {
+    use cify::error::RecordParsingError;
+
+    let raw = r#"HDTPS.UDFROC1.PD2502282802252154DFROC1B       FA280225280226                    
+BSNC123452505182513011111100 POO2N75    113575825 EMU    090      S            P
+"#;
+
+    let error = RecordReader::new(raw.as_bytes())
+        .find_map(Result::err)
+        .ok_or("expected an error")?;
+
+    let Error::Record {
+        line,
+        identity,
+        source:
+            RecordParsingError::InvalidField {
+                field,
+                columns,
+                value,
+            },
+    } = &error
+    else {
+        panic!("unexpected error {error:?}");
+    };
+
+    assert_eq!(*line, 2);
+    assert_eq!(identity, "BS");
+    assert_eq!(*field, "Date Runs To");
+    assert_eq!(*columns, 15..21);
+    assert_eq!(value, "251301");
+    assert_eq!(
+        error.to_string(),
+        "line 2: BS record: invalid Date Runs To \"251301\" in columns 16-21"
+    );
+
+    #[cfg(feature = "serde")]
+    {
+        let bs = raw.lines().nth(1).ok_or("expected a BS record")?;
+
+        assert!(matches!(
+            cify::from_str::<Record>(bs),
+            Err(Error::Record { line: 1, .. })
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json_lines() -> Result<(), Box<dyn std::error::Error>> {
+    let raw = fs::read_to_string("tests/extract.cif")?;
+
+    let mut written = Vec::new();
+    cify::json::write_json_lines(raw.as_bytes(), &mut written)?;
+
+    let lines = String::from_utf8(written)?
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<Vec<serde_json::Value>, _>>()?;
+
+    assert_eq!(lines.len(), 13);
+    assert_eq!(lines[0]["type"], "tiploc_insert");
+    assert_eq!(lines[0]["code"], "AACHEN");
+    assert_eq!(lines[11]["type"], "association");
+    assert_eq!(lines[12]["type"], "schedule");
+    assert_eq!(lines[12]["bs"]["train_uid"], "P13208");
+    assert_eq!(
+        lines[12]["intermediate_locations"].as_array().map(Vec::len),
+        Some(44)
+    );
+
+    let without_trailer = raw.lines().take(12).collect::<Vec<_>>().join("\n");
+    let mut written = Vec::new();
+
+    assert!(matches!(
+        cify::json::write_json_lines(without_trailer.as_bytes(), &mut written),
+        Err(Error::Incomplete)
+    ));
+    assert_eq!(written.iter().filter(|&&b| b == b'\n').count(), 11);
+
+    let mut lines: Vec<_> = raw.lines().collect();
+    let association = lines.remove(12);
+    lines.insert(lines.len() - 1, association);
+
+    let mut written = Vec::new();
+    cify::json::write_json_lines(lines.join("\n").as_bytes(), &mut written)?;
+
+    let types = String::from_utf8(written)?
+        .lines()
+        .map(serde_json::from_str)
+        .map(|line| line.map(|line: serde_json::Value| line["type"].to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(types[11], r#""schedule""#);
+    assert_eq!(types[12], r#""association""#);
+
+    Ok(())
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn stream_schedules() -> Result<(), Box<dyn std::error::Error>> {
+    use cify::stream::RecordStream;
+    use futures_util::TryStreamExt;
+
+    let file = tokio::io::BufReader::new(tokio::fs::File::open("tests/extract.cif").await?);
+
+    let schedules: Vec<_> = RecordStream::new(file).schedules().try_collect().await?;
+
+    assert_eq!(schedules.len(), 1);
+    assert_eq!(schedules[0].intermediate_locations.len(), 44);
+
+    Ok(())
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn read_gzip() -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+
+    let raw = fs::read_to_string("tests/extract.cif")?;
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(raw.as_bytes())?;
+    let compressed = encoder.finish()?;
+
+    let records =
+        RecordReader::decompress(compressed.as_slice())?.collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(records.len(), raw.lines().count());
+
+    let plain = RecordReader::decompress(raw.as_bytes())?.count();
+    assert_eq!(plain, raw.lines().count());
+
+    let timetable = Timetable::from_records(RecordReader::decompress(compressed.as_slice())?)?;
+    assert_eq!(timetable.associations.len(), 1);
+
+    let timetable = Timetable::from_reader(compressed.as_slice())?;
+    assert_eq!(timetable.associations.len(), 1);
+    assert_eq!(timetable.train_schedules.len(), 1);
+
+    #[cfg(feature = "serde")]
+    {
+        let timetable: Timetable = cify::from_reader(compressed.as_slice())?;
+        assert_eq!(timetable.associations.len(), 1);
+    }
+
+    Ok(())
+}
+
+#[cfg(all(feature = "tokio", feature = "gzip"))]
+#[tokio::test]
+async fn stream_gzip() -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write;
+
+    use cify::stream::RecordStream;
+    use flate2::{write::GzEncoder, Compression};
+    use futures_util::TryStreamExt;
+
+    let raw = fs::read_to_string("tests/extract.cif")?;
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(raw.as_bytes())?;
+    let compressed = encoder.finish()?;
+
+    let schedules: Vec<_> = RecordStream::decompress(compressed.as_slice())
+        .await?
+        .schedules()
+        .try_collect()
+        .await?;
+
+    assert_eq!(schedules.len(), 1);
+
+    Ok(())
+}